@@ -0,0 +1,248 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+
+use crate::{
+    metrics::metrics::Metrics,
+    report::{
+        summary::Summary,
+        threshold::{Threshold, ThresholdResult},
+    },
+    requester::error::RequestError,
+    scenario::scenario::ScenarioRequest,
+    scheduler::scheduler::{Executor, Scheduler, SchedulerConfig},
+};
+
+/// How the next rate is chosen while searching for capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SearchStrategy {
+    /// Increase the rate by a fixed step until an objective is violated
+    Step,
+    /// Double the rate until an objective is violated, then bisect
+    Binary,
+}
+
+pub struct CapacitySearch {
+    pub strategy: SearchStrategy,
+    pub start_rate: u64,
+    pub step_rate: u64,
+    pub max_rate: Option<u64>,
+    pub step_duration: u64,
+    pub percentile: f64,
+    pub max_latency: Option<Duration>,
+    pub max_error_rate: f64,
+    /// In-flight slots of every step, by default the requests the rate can
+    /// put in flight within the request timeout
+    pub max_in_flight: Option<u64>,
+    /// Checked against every step next to the latency and error objectives
+    pub thresholds: Vec<Threshold>,
+}
+
+/// Outcome of running the target at a single rate.
+#[derive(Debug, Serialize)]
+pub struct StepResult {
+    pub rate: u64,
+    pub achieved_rps: f64,
+    /// Corrected latency at the objective's percentile
+    #[serde(rename = "latency_us")]
+    pub latency: u64,
+    pub error_rate: f64,
+    /// Iterations the open model could not start because every in-flight
+    /// slot was taken
+    pub dropped: u64,
+    pub passed: bool,
+    pub thresholds: Vec<ThresholdResult>,
+    pub summary: Summary,
+}
+
+/// Machine-readable result of a search, serialised by the JSON output.
+#[derive(Debug, Serialize)]
+pub struct CapacityReport<'a> {
+    pub highest_sustainable_rate: Option<u64>,
+    pub steps: &'a [StepResult],
+}
+
+impl CapacitySearch {
+    /// Runs the scheduler in the open model at increasing rates and returns
+    /// every step that was executed, in execution order.
+    pub async fn run(
+        &self,
+        config: &SchedulerConfig,
+        requests: &[ScenarioRequest],
+    ) -> Result<Vec<StepResult>, RequestError> {
+        let step_rate = self.step_rate.max(1);
+        let mut results = Vec::new();
+        let mut highest_passed: Option<u64> = None;
+        let mut lowest_failed: Option<u64> = None;
+        let mut rate = self.start_rate.max(1);
+
+        loop {
+            let result = self.run_step(config, requests, rate).await?;
+            if result.passed {
+                highest_passed = Some(rate);
+            } else {
+                lowest_failed = Some(rate);
+            }
+            results.push(result);
+
+            rate = match (highest_passed, lowest_failed) {
+                (None, Some(_)) => break,
+                (Some(passed), Some(failed)) => {
+                    if self.strategy == SearchStrategy::Step || failed - passed <= step_rate {
+                        break;
+                    }
+                    passed + (failed - passed) / 2
+                }
+                (Some(passed), None) => {
+                    let next = match self.strategy {
+                        SearchStrategy::Step => passed + step_rate,
+                        SearchStrategy::Binary => passed * 2,
+                    };
+                    match self.max_rate {
+                        Some(max_rate) if passed >= max_rate => break,
+                        Some(max_rate) => next.min(max_rate),
+                        None => next,
+                    }
+                }
+                (None, None) => unreachable!("every step either passes or fails"),
+            };
+        }
+
+        Ok(results)
+    }
+
+    async fn run_step(
+        &self,
+        config: &SchedulerConfig,
+        requests: &[ScenarioRequest],
+        rate: u64,
+    ) -> Result<StepResult, RequestError> {
+        let metrics = Arc::new(Metrics::new());
+
+        // A slot is held for at most the timeout, so that the step drops no
+        // iterations before the target starts to time out
+        let max_in_flight = self
+            .max_in_flight
+            .unwrap_or(rate.saturating_mul(config.timeout.max(1)));
+
+        // Every step uses the data file from the first row, otherwise the
+        // stop-when-exhausted strategy leaves nothing to send for later steps
+        let feeder = match &config.feeder {
+            Some(feeder) => Some(Arc::new(
+                feeder
+                    .restart(max_in_flight)
+                    .map_err(|e| RequestError::ConfigError(e.to_string()))?,
+            )),
+            None => None,
+        };
+
+        let step_config = SchedulerConfig {
+            duration: self.step_duration,
+            rate: Some(rate),
+            executor: Executor::Open,
+            max_in_flight,
+            stages: None,
+            feeder,
+            ..config.clone()
+        };
+
+        let scheduler = Scheduler::new(&metrics, step_config, requests.to_vec());
+        let elapsed = scheduler.run().await?;
+
+        let total = metrics.total_requests().await;
+        let error_rate = percentage(metrics.failed_requests().await, total);
+
+        // Dropped iterations mean the rate was not applied in full, they are
+        // judged by the same limit as failed requests but reported apart
+        let dropped = metrics.dropped_iterations().await;
+        let dropped_rate = percentage(dropped, total + dropped);
+
+        let latency = metrics
+            .corrected_histogram()
+            .await
+            .value_at_quantile(self.percentile / 100.0);
+
+        let latency_ok = self
+            .max_latency
+            .is_none_or(|max_latency| latency <= max_latency.as_micros() as u64);
+
+        let summary = scheduler.summary(elapsed).await;
+        let histogram = metrics.histogram().await;
+        let thresholds: Vec<ThresholdResult> = self
+            .thresholds
+            .iter()
+            .map(|threshold| threshold.evaluate(&summary, &histogram))
+            .collect();
+        let thresholds_ok = thresholds.iter().all(|result| result.passed);
+
+        Ok(StepResult {
+            rate,
+            achieved_rps: metrics.rps(elapsed).await,
+            latency,
+            error_rate,
+            dropped,
+            passed: latency_ok
+                && error_rate <= self.max_error_rate
+                && dropped_rate <= self.max_error_rate
+                && thresholds_ok,
+            thresholds,
+            summary,
+        })
+    }
+
+    pub fn report<'a>(&self, results: &'a [StepResult]) -> CapacityReport<'a> {
+        CapacityReport {
+            highest_sustainable_rate: highest_sustainable_rate(results),
+            steps: results,
+        }
+    }
+
+    pub fn print_report(&self, results: &[StepResult]) {
+        println!();
+        println!(
+            "{:<6} {:>10} {:>12} {:>10} {:>10} {:>10}  Result",
+            "Step",
+            "Rate",
+            "Achieved",
+            format!("P({})", self.percentile),
+            "Errors",
+            "Dropped"
+        );
+        for (step, result) in results.iter().enumerate() {
+            println!(
+                "{:<6} {:>8}/s {:>10.2}/s {:>10} {:>9.2}% {:>10}  {}",
+                step + 1,
+                result.rate,
+                result.achieved_rps,
+                Metrics::format_micros(result.latency),
+                result.error_rate,
+                result.dropped,
+                if result.passed { "ok" } else { "violated" }
+            );
+            for threshold in result.thresholds.iter().filter(|result| !result.passed) {
+                println!("{:<6} threshold {} FAILED", "", threshold.expression);
+            }
+        }
+        println!();
+
+        match highest_sustainable_rate(results) {
+            Some(rate) => println!("Highest sustainable rate:......{}/s", rate),
+            None => println!(
+                "Highest sustainable rate:......none, {}/s already violates the objectives",
+                self.start_rate
+            ),
+        }
+    }
+}
+
+fn highest_sustainable_rate(results: &[StepResult]) -> Option<u64> {
+    results.iter().filter(|r| r.passed).map(|r| r.rate).max()
+}
+
+fn percentage(part: u64, total: u64) -> f64 {
+    if total > 0 {
+        part as f64 / total as f64 * 100.0
+    } else {
+        0.0
+    }
+}
@@ -1,5 +1,22 @@
-use clap::{Parser, Subcommand};
-use reqwest::Method;
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Args, Parser, Subcommand};
+use http::Method;
+use tonic::metadata::MetadataMap;
+
+use crate::{
+    capacity::search::SearchStrategy,
+    report::{output::Output, threshold::Threshold},
+    requester::{
+        check::Check,
+        client::ConnectionOptions,
+        cookie::CookieMode,
+        grpc_requester::{self, GrpcCompression},
+        tls::TlsOptions,
+    },
+    scheduler::{scheduler::Executor, stages::Stages},
+    template::feeder::FeedStrategy,
+};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -43,6 +60,113 @@ pub struct Cli {
     )]
     pub timeout: u64,
 
+    #[arg(
+        long,
+        value_enum,
+        value_name = "EXECUTOR",
+        default_value_t = Executor::Closed,
+        help = "Load model: closed waits for responses, open keeps a constant arrival rate"
+    )]
+    pub executor: Executor,
+
+    #[arg(
+        long,
+        value_name = "MAX_IN_FLIGHT",
+        help = "Maximum number of in-flight requests in the open model [default: concurrency, rate x timeout in find-capacity]"
+    )]
+    pub max_in_flight: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "STAGES",
+        help = "Load stages as DURATION:TARGET pairs, for example 30s:10,2m:200,30s:0. The target is the number of workers in the closed model and the rate in the open one"
+    )]
+    pub stages: Option<Stages>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 1,
+        help = "Length of the intervals metrics are collected in"
+    )]
+    pub snapshot_interval: u64,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Print per-interval metrics in the report"
+    )]
+    pub timeline: bool,
+
+    #[arg(
+        long,
+        value_name = "TAG",
+        default_value = "name",
+        help = "Tag to group the results of the requests by: name, protocol, method, url or a tag of the scenario"
+    )]
+    pub group_by: String,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Render {{...}} expressions in URLs, headers and bodies, on by default with --data-file or extracted values"
+    )]
+    pub templates: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "CSV or JSONL file whose rows are bound to template variables, one row per iteration"
+    )]
+    pub data_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STRATEGY",
+        default_value_t = FeedStrategy::Sequential,
+        help = "Order in which the rows of the data file are used"
+    )]
+    pub data_strategy: FeedStrategy,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        default_value_t = CookieMode::Off,
+        help = "Keep the cookies set by HTTP responses per worker for the whole run or only for one iteration"
+    )]
+    pub cookie_jar: CookieMode,
+
+    #[arg(
+        long = "cookie",
+        value_name = "NAME=VALUE",
+        value_parser = parse_cookie,
+        help = "Cookie every worker starts with, sent to every host (repeatable)"
+    )]
+    pub cookies: Vec<(String, String)>,
+
+    #[command(flatten)]
+    pub tls: TlsOptions,
+
+    #[command(flatten)]
+    pub connection: ConnectionOptions,
+
+    #[arg(
+        short,
+        long,
+        value_name = "FORMAT=PATH",
+        help = "Write the report to a file, for example json=report.json or json=- for stdout (repeatable)"
+    )]
+    pub output: Vec<Output>,
+
+    #[arg(
+        long,
+        value_name = "EXPRESSION",
+        help = "Fail the run unless the condition holds, for example p99<200ms, error_rate<0.1% or rps>5000 (repeatable)"
+    )]
+    pub threshold: Vec<Threshold>,
+
     #[arg(
         long = "no-progress",
         default_value_t = false,
@@ -59,6 +183,81 @@ pub struct Cli {
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
+    #[command(flatten)]
+    Target(Target),
+
+    /// Search for the highest request rate the target sustains
+    FindCapacity(FindCapacityArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct FindCapacityArgs {
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STRATEGY",
+        default_value_t = SearchStrategy::Step,
+        help = "Increase the rate by a fixed step, or double it and bisect after the first failure"
+    )]
+    pub search: SearchStrategy,
+
+    #[arg(
+        long,
+        value_name = "RATE",
+        default_value_t = 100,
+        help = "Rate of the first step in requests per second"
+    )]
+    pub start_rate: u64,
+
+    #[arg(
+        long,
+        value_name = "RATE",
+        default_value_t = 100,
+        help = "Rate increment between steps, and the precision of the binary search"
+    )]
+    pub step_rate: u64,
+
+    #[arg(long, value_name = "RATE", help = "Highest rate to try")]
+    pub max_rate: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value_t = 10,
+        help = "Duration of each step in seconds"
+    )]
+    pub step_duration: u64,
+
+    #[arg(
+        long,
+        value_name = "PERCENTILE",
+        default_value_t = 99.0,
+        help = "Latency percentile the latency objective applies to"
+    )]
+    pub percentile: f64,
+
+    #[arg(
+        long,
+        value_name = "LATENCY",
+        value_parser = humantime::parse_duration,
+        help = "Highest acceptable latency at the percentile, for example 200ms"
+    )]
+    pub max_latency: Option<Duration>,
+
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 1.0,
+        help = "Highest acceptable percentage of failed or dropped requests"
+    )]
+    pub max_error_rate: f64,
+
+    #[command(subcommand)]
+    pub target: Target,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Target {
     /// HTTP load testing
     Http {
         #[arg(
@@ -81,9 +280,18 @@ pub enum Command {
 
         #[arg(short = 'F', long = "form", help = "Form parameters (repeatable)")]
         form: Vec<String>,
+
+        #[arg(
+            long = "check",
+            value_name = "CHECK",
+            value_parser = Check::parse_http,
+            help = "Response check, for example status:200,201, header:Content-Type=application/json, body-contains:ok, body-regex:id=\\d+, json:$.status=active or max-body-size:1024 (repeatable)"
+        )]
+        checks: Vec<Check>,
     },
 
     /// gRPC load testing
+    #[command(subcommand_negates_reqs = true)]
     Grpc {
         #[arg(
             short,
@@ -93,19 +301,77 @@ pub enum Command {
         )]
         address: String,
 
-        #[arg(long, value_name = "PROTO", help = "Path to the proto file")]
-        proto: String,
+        #[arg(
+            long,
+            value_name = "PROTO",
+            help = "Path to a proto file, or a descriptor set such as one built with buf build -o image.binpb (repeatable) [default: fetched with server reflection]"
+        )]
+        proto: Vec<String>,
+
+        #[arg(
+            short = 'I',
+            long = "import-path",
+            value_name = "DIR",
+            help = "Directory the imports of the proto files are looked up in, before the directory of each proto file (repeatable)"
+        )]
+        import_paths: Vec<String>,
 
         #[arg(
             short = 'X',
             long,
             value_name = "METHOD",
+            required = true,
             help = "GRPC method for example UserService.GetUser"
         )]
-        method: String,
+        method: Option<String>,
 
         #[arg(short, long, value_name = "DATA", help = "Data to send")]
         data: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "MESSAGES",
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Number of messages sent per call by client streaming and bidirectional streaming methods"
+        )]
+        messages: u64,
+
+        #[arg(
+            short = 'H',
+            long = "header",
+            value_name = "KEY:VALUE",
+            value_parser = parse_metadata,
+            help = "Metadata sent with every call, for example authorization:Bearer TOKEN, values of keys ending in -bin are base64 (repeatable)"
+        )]
+        metadata: Vec<(String, String)>,
+
+        #[arg(
+            long,
+            value_name = "DURATION",
+            value_parser = humantime::parse_duration,
+            help = "Deadline of every call sent to the server as grpc-timeout, for example 500ms"
+        )]
+        deadline: Option<Duration>,
+
+        #[arg(
+            long,
+            value_enum,
+            value_name = "ENCODING",
+            help = "Compress request messages and accept responses compressed with the same encoding"
+        )]
+        compression: Option<GrpcCompression>,
+
+        #[arg(
+            long = "check",
+            value_name = "CHECK",
+            value_parser = Check::parse_grpc,
+            help = "Response check, for example code:OK,NOT_FOUND to expect codes other than OK, json:$.items[0].id=1, header:x-served-by=api, body-contains:ok or max-body-size:1024 (repeatable)"
+        )]
+        checks: Vec<Check>,
+
+        #[command(subcommand)]
+        action: Option<GrpcAction>,
     },
     /// Websocket load testing
     Websocket {
@@ -115,4 +381,47 @@ pub enum Command {
         #[arg(short, long, value_name = "DATA", help = "Data to send")]
         data: String,
     },
+
+    /// Run the load test described in a YAML or TOML scenario file
+    Run {
+        #[arg(
+            value_name = "SCENARIO",
+            help = "Path to the scenario file, options given on the command line override its settings"
+        )]
+        scenario: PathBuf,
+    },
+}
+
+/// Commands that inspect the services of a gRPC server instead of load
+/// testing it.
+#[derive(Subcommand, Debug)]
+pub enum GrpcAction {
+    /// List the services of the server and their methods
+    List,
+    /// Print the definition of a service, method, message or enum
+    Describe {
+        #[arg(
+            value_name = "SYMBOL",
+            help = "Fully qualified name, for example helloworld.Greeter or helloworld.HelloRequest"
+        )]
+        symbol: String,
+    },
+}
+
+fn parse_metadata(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("invalid metadata '{}': expected KEY:VALUE", s))?;
+    grpc_requester::insert_metadata(&mut MetadataMap::new(), key, value)?;
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn parse_cookie(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("invalid cookie '{}': expected NAME=VALUE", s)),
+    }
 }
@@ -1,4 +1,8 @@
+pub mod capacity;
 pub mod commands;
 pub mod metrics;
+pub mod report;
 pub mod requester;
+pub mod scenario;
 pub mod scheduler;
+pub mod template;
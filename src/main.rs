@@ -1,18 +1,88 @@
 use std::{collections::HashMap, sync::Arc};
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use hammerload::{
-    commands::{Cli, Command},
+    capacity::search::CapacitySearch,
+    commands::{Cli, Command, GrpcAction, Target},
     metrics::metrics::Metrics,
-    requester::params::RequestParams,
-    scheduler::scheduler::Scheduler,
+    report::threshold::{self, THRESHOLDS_FAILED_EXIT_CODE},
+    requester::{
+        cookie::CookieJar, descriptors, grpc_requester, params::RequestParams, proxy::ProxyConfig,
+        tls::TlsConfig,
+    },
+    scenario::scenario::{Scenario, ScenarioRequest},
+    scheduler::scheduler::{Executor, Scheduler, SchedulerConfig},
+    template::feeder::Feeder,
 };
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
-    if !cli.no_logo {
+    let target = match &cli.command {
+        Command::Target(target) => target,
+        Command::FindCapacity(args) => &args.target,
+    };
+    let scenario = match target {
+        Target::Run { scenario } => Some(Scenario::load(scenario)?),
+        _ => None,
+    };
+    if let Some(scenario) = &scenario {
+        scenario.apply(&mut cli, &matches);
+    }
+
+    if let Command::Target(Target::Grpc {
+        address,
+        proto,
+        import_paths,
+        action: Some(action),
+        ..
+    }) = &cli.command
+    {
+        let sources = descriptors::ProtoSources {
+            files: proto.clone(),
+            import_paths: import_paths.clone(),
+        };
+        // The server is only asked when there are no proto files to read
+        let channel = if sources.is_empty() {
+            let tls = TlsConfig::load(&cli.tls)?;
+            let metrics = Arc::new(Metrics::new());
+            let channel = grpc_requester::connect(address, &metrics, &tls, cli.timeout)
+                .await
+                .map_err(|e| e.to_string())?;
+            Some(channel)
+        } else {
+            None
+        };
+
+        let output = match action {
+            GrpcAction::List => descriptors::list(&sources, channel.as_ref()).await?,
+            GrpcAction::Describe { symbol } => {
+                descriptors::describe(&sources, channel.as_ref(), symbol).await?
+            }
+        };
+        print!("{}", output);
+
+        return Ok(());
+    }
+
+    if matches!(cli.command, Command::Target(_))
+        && cli.executor == Executor::Open
+        && cli.rate.is_none()
+        && cli.stages.is_none()
+    {
+        return Err("The open executor requires --rate or --stages".into());
+    }
+
+    if cli.connection.max_concurrent_streams.is_some() && cli.connection.clients.is_none() {
+        return Err("--max-concurrent-streams requires --clients".into());
+    }
+
+    // Keep stdout parseable when a machine-readable report is written to it
+    let report_to_stdout = cli.output.iter().any(|output| output.is_stdout());
+
+    if !cli.no_logo && !report_to_stdout {
         let logo = r#"
     ██╗  ██╗ █████╗ ███╗   ███╗███╗   ███╗███████╗██████╗ ██╗      ██████╗  █████╗ ██████╗
     ██║  ██║██╔══██╗████╗ ████║████╗ ████║██╔════╝██╔══██╗██║     ██╔═══██╗██╔══██╗██╔══██╗
@@ -26,41 +96,147 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let metrics = Arc::new(Metrics::new());
 
-    let request_params = parse_request_params(cli.command);
+    // Stages define the length of the test and, in the closed model, the peak
+    // number of workers the rate is split between
+    let (concurrency, duration) = match &cli.stages {
+        Some(stages) if cli.executor == Executor::Closed => (
+            stages.peak().max(1),
+            stages.duration().as_secs_f64().ceil() as u64,
+        ),
+        Some(stages) => (
+            cli.concurrency,
+            stages.duration().as_secs_f64().ceil() as u64,
+        ),
+        None => (cli.concurrency, cli.duration),
+    };
+
+    let max_in_flight = cli.max_in_flight.unwrap_or(cli.concurrency);
+
+    // Rows are split between the workers that run iterations: the closed
+    // model workers or the open model in-flight slots
+    let feeder = match &cli.data_file {
+        Some(path) => {
+            let workers = match cli.executor {
+                Executor::Closed => concurrency,
+                Executor::Open => max_in_flight,
+            };
+            Some(Arc::new(Feeder::load(path, cli.data_strategy, workers)?))
+        }
+        None => None,
+    };
+
+    let config = SchedulerConfig {
+        concurrency,
+        duration,
+        rate: cli.rate,
+        timeout: cli.timeout,
+        show_progress: !cli.no_progress,
+        executor: cli.executor,
+        max_in_flight,
+        stages: cli.stages,
+        snapshot_interval: cli.snapshot_interval,
+        show_timeline: cli.timeline,
+        group_by: cli.group_by,
+        feeder,
+        cookies: CookieJar::new(cli.cookie_jar, cli.cookies),
+        tls: Arc::new(TlsConfig::load(&cli.tls)?),
+        proxy: Arc::new(ProxyConfig::from_env()?),
+        connection: cli.connection,
+        templates: cli.templates,
+        flow: scenario
+            .as_ref()
+            .map(|scenario| scenario.flow)
+            .unwrap_or_default(),
+    };
+
+    match cli.command {
+        Command::Target(target) => {
+            let request_params = parse_request_params(target, scenario);
+
+            let scheduler = Scheduler::new(&metrics, config, request_params);
+
+            let elapsed = scheduler.run().await.map_err(|e| e.to_string())?;
+
+            let mut summary = scheduler.summary(elapsed).await;
+            let histogram = metrics.histogram().await;
+            summary.thresholds = cli
+                .threshold
+                .iter()
+                .map(|threshold| threshold.evaluate(&summary, &histogram))
+                .collect();
+
+            if !report_to_stdout {
+                scheduler.print_report(elapsed).await;
+                threshold::print_results(&cli.threshold, &summary.thresholds);
+            }
+
+            for output in &cli.output {
+                output.write(&summary)?;
+            }
+
+            let failed: Vec<&str> = summary
+                .thresholds
+                .iter()
+                .filter(|result| !result.passed)
+                .map(|result| result.expression.as_str())
+                .collect();
+            if !failed.is_empty() {
+                eprintln!("Thresholds failed: {}", failed.join(", "));
+                std::process::exit(THRESHOLDS_FAILED_EXIT_CODE);
+            }
+        }
+        Command::FindCapacity(args) => {
+            let request_params = parse_request_params(args.target, scenario);
 
-    let scheduler = Scheduler::new(
-        &metrics,
-        cli.concurrency,
-        cli.duration,
-        cli.rate,
-        cli.timeout,
-        !cli.no_progress,
-        request_params,
-    );
+            let search = CapacitySearch {
+                strategy: args.search,
+                start_rate: args.start_rate,
+                step_rate: args.step_rate,
+                max_rate: args.max_rate,
+                step_duration: args.step_duration,
+                percentile: args.percentile,
+                max_latency: args.max_latency,
+                max_error_rate: args.max_error_rate,
+                max_in_flight: cli.max_in_flight,
+                thresholds: cli.threshold,
+            };
 
-    scheduler.run().await;
+            let results = search
+                .run(&config, &request_params)
+                .await
+                .map_err(|e| e.to_string())?;
+            if !report_to_stdout {
+                search.print_report(&results);
+            }
+
+            for output in &cli.output {
+                output.write(&search.report(&results))?;
+            }
+        }
+    }
 
     Ok(())
 }
 
-fn parse_request_params(command: Command) -> RequestParams {
-    match command {
-        Command::Http {
+fn parse_request_params(target: Target, scenario: Option<Scenario>) -> Vec<ScenarioRequest> {
+    let request_params = match target {
+        Target::Http {
             url,
             method,
             body,
             headers,
             form,
+            checks,
         } => {
             let mut form_params = HashMap::new();
-            let mut header_map = reqwest::header::HeaderMap::new();
+            let mut header_map = http::header::HeaderMap::new();
 
             for h in &headers {
                 match h.split_once(':') {
                     Some((key, value)) => {
                         if let (Ok(header_name), Ok(header_value)) = (
-                            reqwest::header::HeaderName::from_bytes(key.trim().as_bytes()),
-                            reqwest::header::HeaderValue::from_str(value.trim()),
+                            http::header::HeaderName::from_bytes(key.trim().as_bytes()),
+                            http::header::HeaderValue::from_str(value.trim()),
                         ) {
                             header_map.insert(header_name, header_value);
                         } else {
@@ -89,21 +265,53 @@ fn parse_request_params(command: Command) -> RequestParams {
                 body,
                 headers: header_map,
                 form: form_params,
+                checks,
+                extract: Vec::new(),
             })
         }
-        Command::Grpc {
-            address,
-            proto,
-            method,
-            data,
-        } => RequestParams::Grpc(hammerload::requester::params::GrpcParams {
+        Target::Grpc {
             address,
             proto,
+            import_paths,
             method,
             data,
-        }),
-        Command::Websocket { url, data } => {
+            messages,
+            metadata,
+            deadline,
+            compression,
+            checks,
+            ..
+        } => {
+            let mut metadata_map = tonic::metadata::MetadataMap::new();
+            for (key, value) in &metadata {
+                // Already validated while parsing the arguments
+                let _ = grpc_requester::insert_metadata(&mut metadata_map, key, value);
+            }
+
+            RequestParams::Grpc(hammerload::requester::params::GrpcParams {
+                address,
+                protos: descriptors::ProtoSources {
+                    files: proto,
+                    import_paths,
+                },
+                method: method.unwrap_or_default(),
+                data,
+                messages,
+                metadata: metadata_map,
+                deadline,
+                compression,
+                checks,
+            })
+        }
+        Target::Websocket { url, data } => {
             RequestParams::Websocket(hammerload::requester::params::WebsocketParams { url, data })
         }
-    }
+        Target::Run { .. } => {
+            return scenario
+                .map(|scenario| scenario.requests)
+                .unwrap_or_default()
+        }
+    };
+
+    vec![ScenarioRequest::new(request_params)]
 }
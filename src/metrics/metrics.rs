@@ -1,12 +1,32 @@
 use hdrhistogram::Histogram;
-use std::sync::{
-    atomic::{AtomicU64, Ordering},
-    Arc,
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 use tokio::sync::Mutex;
 
+use crate::{
+    metrics::{
+        snapshot::{LatencySummary, Snapshot},
+        timing::Timing,
+    },
+    requester::error::RequestError,
+};
+
+/// Metrics of the whole run. A handle created with `for_request` records into
+/// the same metrics and also into the stats of one request of the scenario.
 pub struct Metrics {
-    hist: Arc<Mutex<Histogram<u64>>>,
+    run: Arc<RunMetrics>,
+    request: Option<Arc<RequestStats>>,
+}
+
+struct RunMetrics {
+    latency: Mutex<Latencies>,
+    corrected_hist: Mutex<Histogram<u64>>,
     min_latency: AtomicU64,
     max_latency: AtomicU64,
     bytes_sent: AtomicU64,
@@ -14,108 +34,504 @@ pub struct Metrics {
     total_requests: AtomicU64,
     successful_requests: AtomicU64,
     failed_requests: AtomicU64,
+    dropped_iterations: AtomicU64,
+    connections_opened: AtomicU64,
+    messages_sent: AtomicU64,
+    messages_received: AtomicU64,
+    compressed_bytes_sent: AtomicU64,
+    compressed_bytes_received: AtomicU64,
+    uncompressed_bytes_sent: AtomicU64,
+    uncompressed_bytes_received: AtomicU64,
+    errors: Mutex<BTreeMap<&'static str, ErrorStats>>,
+    checks: Mutex<BTreeMap<String, CheckStats>>,
+    http_statuses: Mutex<BTreeMap<u16, u64>>,
+    http_versions: Mutex<BTreeMap<&'static str, u64>>,
+    grpc_codes: Mutex<BTreeMap<i32, u64>>,
+    timings: Mutex<BTreeMap<Timing, Histogram<u64>>>,
+    interval_counters: IntervalCounters,
+    snapshots: Mutex<Vec<Snapshot>>,
+}
+
+/// Number of failures of one `RequestError` kind, with the first message seen
+/// to tell what went wrong without printing every failure.
+#[derive(Debug, Clone)]
+pub struct ErrorStats {
+    pub count: u64,
+    pub example: String,
+}
+
+/// Number of responses that passed and failed one check.
+#[derive(Debug, Clone, Default)]
+pub struct CheckStats {
+    pub passed: u64,
+    pub failed: u64,
+}
+
+/// Results of one request of the traffic mix. Only what the report breaks
+/// down by request is kept, everything else is recorded for the whole run.
+pub struct RequestStats {
+    count: AtomicU64,
+    failed: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    hist: Mutex<Histogram<u64>>,
+}
+
+/// Latency distributions of the whole test and of the interval that is
+/// currently being collected, behind one lock as they are recorded together.
+struct Latencies {
+    hist: Histogram<u64>,
+    interval: Histogram<u64>,
+    interval_started: Duration,
+}
+
+/// Counters of the interval that is currently being collected, reset by
+/// `take_snapshot`.
+#[derive(Default)]
+struct IntervalCounters {
+    requests: AtomicU64,
+    failed_requests: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for RequestStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestStats {
+    pub fn new() -> Self {
+        RequestStats {
+            count: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            hist: Mutex::new(Histogram::<u64>::new(3).unwrap()),
+        }
+    }
+
+    pub async fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub async fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    pub async fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub async fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub async fn histogram(&self) -> Histogram<u64> {
+        self.hist.lock().await.clone()
+    }
 }
 
 impl Metrics {
     pub fn new() -> Self {
         let hist = Histogram::<u64>::new(3).unwrap();
         Self {
-            hist: Arc::new(Mutex::new(hist)),
-            min_latency: AtomicU64::new(u64::MAX),
-            max_latency: AtomicU64::new(0),
-            bytes_sent: AtomicU64::new(0),
-            bytes_received: AtomicU64::new(0),
-            total_requests: AtomicU64::new(0),
-            successful_requests: AtomicU64::new(0),
-            failed_requests: AtomicU64::new(0),
+            run: Arc::new(RunMetrics {
+                latency: Mutex::new(Latencies {
+                    hist: hist.clone(),
+                    interval: hist.clone(),
+                    interval_started: Duration::ZERO,
+                }),
+                corrected_hist: Mutex::new(hist),
+                min_latency: AtomicU64::new(u64::MAX),
+                max_latency: AtomicU64::new(0),
+                bytes_sent: AtomicU64::new(0),
+                bytes_received: AtomicU64::new(0),
+                total_requests: AtomicU64::new(0),
+                successful_requests: AtomicU64::new(0),
+                failed_requests: AtomicU64::new(0),
+                dropped_iterations: AtomicU64::new(0),
+                connections_opened: AtomicU64::new(0),
+                messages_sent: AtomicU64::new(0),
+                messages_received: AtomicU64::new(0),
+                compressed_bytes_sent: AtomicU64::new(0),
+                compressed_bytes_received: AtomicU64::new(0),
+                uncompressed_bytes_sent: AtomicU64::new(0),
+                uncompressed_bytes_received: AtomicU64::new(0),
+                errors: Mutex::new(BTreeMap::new()),
+                checks: Mutex::new(BTreeMap::new()),
+                http_statuses: Mutex::new(BTreeMap::new()),
+                http_versions: Mutex::new(BTreeMap::new()),
+                grpc_codes: Mutex::new(BTreeMap::new()),
+                timings: Mutex::new(BTreeMap::new()),
+                interval_counters: IntervalCounters::default(),
+                snapshots: Mutex::new(Vec::new()),
+            }),
+            request: None,
+        }
+    }
+
+    /// Creates a handle that records into these metrics and into `stats`, the
+    /// results of one request of the scenario.
+    pub fn for_request(&self, stats: Arc<RequestStats>) -> Self {
+        Self {
+            run: Arc::clone(&self.run),
+            request: Some(stats),
         }
     }
 
     pub async fn increment_total_requests(&self) {
-        self.total_requests.fetch_add(1, Ordering::Relaxed);
+        self.run.total_requests.fetch_add(1, Ordering::Relaxed);
+        self.run
+            .interval_counters
+            .requests
+            .fetch_add(1, Ordering::Relaxed);
+        if let Some(request) = &self.request {
+            request.count.fetch_add(1, Ordering::Relaxed);
+        }
     }
 
     pub async fn increment_successful_requests(&self) {
-        self.successful_requests.fetch_add(1, Ordering::Relaxed);
+        self.run.successful_requests.fetch_add(1, Ordering::Relaxed);
     }
 
     pub async fn increment_failed_requests(&self) {
-        self.failed_requests.fetch_add(1, Ordering::Relaxed);
+        self.run.failed_requests.fetch_add(1, Ordering::Relaxed);
+        self.run
+            .interval_counters
+            .failed_requests
+            .fetch_add(1, Ordering::Relaxed);
+        if let Some(request) = &self.request {
+            request.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub async fn record_error(&self, error: &RequestError) {
+        self.run
+            .errors
+            .lock()
+            .await
+            .entry(error.kind())
+            .or_insert_with(|| ErrorStats {
+                count: 0,
+                example: error.to_string(),
+            })
+            .count += 1;
+    }
+
+    pub async fn errors(&self) -> BTreeMap<&'static str, ErrorStats> {
+        self.run.errors.lock().await.clone()
+    }
+
+    pub async fn record_check(&self, name: &str, passed: bool) {
+        let mut checks = self.run.checks.lock().await;
+        let stats = checks.entry(name.to_string()).or_default();
+        if passed {
+            stats.passed += 1;
+        } else {
+            stats.failed += 1;
+        }
+    }
+
+    pub async fn checks(&self) -> BTreeMap<String, CheckStats> {
+        self.run.checks.lock().await.clone()
+    }
+
+    pub async fn record_http_status(&self, status: u16) {
+        *self
+            .run
+            .http_statuses
+            .lock()
+            .await
+            .entry(status)
+            .or_insert(0) += 1;
+    }
+
+    pub async fn http_statuses(&self) -> BTreeMap<u16, u64> {
+        self.run.http_statuses.lock().await.clone()
+    }
+
+    /// Counts a response by the HTTP version it was received with.
+    pub async fn record_http_version(&self, version: http::Version) {
+        let version = match version {
+            http::Version::HTTP_09 => "HTTP/0.9",
+            http::Version::HTTP_10 => "HTTP/1.0",
+            http::Version::HTTP_11 => "HTTP/1.1",
+            http::Version::HTTP_2 => "HTTP/2",
+            http::Version::HTTP_3 => "HTTP/3",
+            _ => "unknown",
+        };
+        *self
+            .run
+            .http_versions
+            .lock()
+            .await
+            .entry(version)
+            .or_insert(0) += 1;
+    }
+
+    pub async fn http_versions(&self) -> BTreeMap<&'static str, u64> {
+        self.run.http_versions.lock().await.clone()
+    }
+
+    pub async fn record_grpc_code(&self, code: tonic::Code) {
+        *self
+            .run
+            .grpc_codes
+            .lock()
+            .await
+            .entry(code as i32)
+            .or_insert(0) += 1;
+    }
+
+    /// Returns the counts ordered by the numeric value of the code.
+    pub async fn grpc_codes(&self) -> Vec<(tonic::Code, u64)> {
+        self.run
+            .grpc_codes
+            .lock()
+            .await
+            .iter()
+            .map(|(code, count)| (tonic::Code::from_i32(*code), *count))
+            .collect()
+    }
+
+    pub async fn increment_dropped_iterations(&self) {
+        self.run.dropped_iterations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub async fn increment_connections_opened(&self) {
+        self.run.connections_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts gRPC messages sent, one per unary call and every message of a
+    /// request stream.
+    pub async fn add_messages_sent(&self, messages: u64) {
+        self.run
+            .messages_sent
+            .fetch_add(messages, Ordering::Relaxed);
+    }
+
+    pub async fn add_messages_received(&self, messages: u64) {
+        self.run
+            .messages_received
+            .fetch_add(messages, Ordering::Relaxed);
+    }
+
+    /// Counts the bytes of compressed gRPC messages with their uncompressed
+    /// size. `add_bytes_sent` counts the uncompressed size of every request,
+    /// which also includes requests that are not compressed.
+    pub async fn add_compressed_bytes_sent(&self, bytes: u64, uncompressed: u64) {
+        self.run
+            .compressed_bytes_sent
+            .fetch_add(bytes, Ordering::Relaxed);
+        self.run
+            .uncompressed_bytes_sent
+            .fetch_add(uncompressed, Ordering::Relaxed);
+    }
+
+    pub async fn add_compressed_bytes_received(&self, bytes: u64, uncompressed: u64) {
+        self.run
+            .compressed_bytes_received
+            .fetch_add(bytes, Ordering::Relaxed);
+        self.run
+            .uncompressed_bytes_received
+            .fetch_add(uncompressed, Ordering::Relaxed);
     }
 
     pub async fn add_bytes_sent(&self, bytes: u64) {
-        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        self.run.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        self.run
+            .interval_counters
+            .bytes_sent
+            .fetch_add(bytes, Ordering::Relaxed);
+        if let Some(request) = &self.request {
+            request.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        }
     }
 
     pub async fn add_bytes_received(&self, bytes: u64) {
-        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+        self.run.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+        self.run
+            .interval_counters
+            .bytes_received
+            .fetch_add(bytes, Ordering::Relaxed);
+        if let Some(request) = &self.request {
+            request.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+        }
     }
 
     pub async fn total_requests(&self) -> u64 {
-        self.total_requests.load(Ordering::Relaxed)
+        self.run.total_requests.load(Ordering::Relaxed)
     }
 
     pub async fn successful_requests(&self) -> u64 {
-        self.successful_requests.load(Ordering::Relaxed)
+        self.run.successful_requests.load(Ordering::Relaxed)
     }
 
     pub async fn failed_requests(&self) -> u64 {
-        self.failed_requests.load(Ordering::Relaxed)
+        self.run.failed_requests.load(Ordering::Relaxed)
+    }
+
+    pub async fn dropped_iterations(&self) -> u64 {
+        self.run.dropped_iterations.load(Ordering::Relaxed)
+    }
+
+    pub async fn connections_opened(&self) -> u64 {
+        self.run.connections_opened.load(Ordering::Relaxed)
+    }
+
+    pub async fn messages_sent(&self) -> u64 {
+        self.run.messages_sent.load(Ordering::Relaxed)
+    }
+
+    pub async fn messages_received(&self) -> u64 {
+        self.run.messages_received.load(Ordering::Relaxed)
+    }
+
+    pub async fn compressed_bytes_sent(&self) -> u64 {
+        self.run.compressed_bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub async fn compressed_bytes_received(&self) -> u64 {
+        self.run.compressed_bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Uncompressed size of the messages counted by `compressed_bytes_sent`.
+    pub async fn uncompressed_bytes_sent(&self) -> u64 {
+        self.run.uncompressed_bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub async fn uncompressed_bytes_received(&self) -> u64 {
+        self.run.uncompressed_bytes_received.load(Ordering::Relaxed)
     }
 
     pub async fn bytes_sent(&self) -> u64 {
-        self.bytes_sent.load(Ordering::Relaxed)
+        self.run.bytes_sent.load(Ordering::Relaxed)
     }
 
     pub async fn bytes_received(&self) -> u64 {
-        self.bytes_received.load(Ordering::Relaxed)
+        self.run.bytes_received.load(Ordering::Relaxed)
     }
 
-    pub async fn rps(&self, start_time: std::time::Instant) -> f64 {
+    /// Requests per second over `elapsed`, the duration of the run.
+    pub async fn rps(&self, elapsed: Duration) -> f64 {
         let total = self.total_requests().await;
-        let elapsed = std::time::Instant::now().duration_since(start_time);
-        total as f64 / elapsed.as_millis() as f64 * 1000.0
+        total as f64 / elapsed.as_secs_f64()
     }
 
-    pub async fn throughput_sent(&self, start_time: std::time::Instant) -> f64 {
-        let elapsed = start_time.elapsed().as_secs_f64();
-        let total_bytes = self.bytes_sent.load(Ordering::Relaxed);
-        total_bytes as f64 / elapsed
+    pub async fn throughput_sent(&self, elapsed: Duration) -> f64 {
+        let total_bytes = self.run.bytes_sent.load(Ordering::Relaxed);
+        total_bytes as f64 / elapsed.as_secs_f64()
     }
 
-    pub async fn throughput_received(&self, start_time: std::time::Instant) -> f64 {
-        let elapsed = start_time.elapsed().as_secs_f64();
-        let total_bytes = self.bytes_received.load(Ordering::Relaxed);
-        total_bytes as f64 / elapsed
+    pub async fn throughput_received(&self, elapsed: Duration) -> f64 {
+        let total_bytes = self.run.bytes_received.load(Ordering::Relaxed);
+        total_bytes as f64 / elapsed.as_secs_f64()
     }
 
     pub async fn record_latency(&self, latency: u64) {
-        let mut hist = self.hist.lock().await;
-        hist.record(latency).unwrap();
+        {
+            let mut latencies = self.run.latency.lock().await;
+            latencies.hist.record(latency).unwrap();
+            latencies.interval.record(latency).unwrap();
+        }
+        if let Some(request) = &self.request {
+            request.hist.lock().await.record(latency).unwrap();
+        }
+
+        self.run.min_latency.fetch_min(latency, Ordering::Relaxed);
+        self.run.max_latency.fetch_max(latency, Ordering::Relaxed);
+    }
 
-        self.min_latency.store(
-            latency.min(self.min_latency.load(Ordering::Relaxed)),
-            Ordering::Relaxed,
-        );
-        self.max_latency.store(
-            latency.max(self.max_latency.load(Ordering::Relaxed)),
-            Ordering::Relaxed,
-        );
+    /// Records a latency measured from the intended send time. A non-zero
+    /// `expected_interval` backfills the samples a stalled worker did not send.
+    pub async fn record_corrected_latency(&self, latency: u64, expected_interval: u64) {
+        let mut hist = self.run.corrected_hist.lock().await;
+        hist.record_correct(latency, expected_interval).unwrap();
+    }
+
+    pub async fn corrected_histogram(&self) -> Histogram<u64> {
+        self.run.corrected_hist.lock().await.clone()
     }
 
     pub async fn histogram(&self) -> Histogram<u64> {
-        self.hist.lock().await.clone()
+        self.run.latency.lock().await.hist.clone()
+    }
+
+    /// Records the duration of a single phase of a request in microseconds.
+    pub async fn record_timing(&self, timing: Timing, duration: u64) {
+        self.run
+            .timings
+            .lock()
+            .await
+            .entry(timing)
+            .or_insert_with(|| Histogram::<u64>::new(3).unwrap())
+            .record(duration)
+            .unwrap();
+    }
+
+    pub async fn timings(&self) -> BTreeMap<Timing, Histogram<u64>> {
+        self.run.timings.lock().await.clone()
+    }
+
+    /// Closes the current interval at `elapsed` since the start of the test
+    /// and starts collecting the next one.
+    pub async fn take_snapshot(&self, elapsed: Duration) -> Snapshot {
+        let mut latencies = self.run.latency.lock().await;
+        let counters = &self.run.interval_counters;
+
+        let snapshot = Snapshot {
+            elapsed,
+            interval: elapsed.saturating_sub(latencies.interval_started),
+            requests: counters.requests.swap(0, Ordering::Relaxed),
+            failed_requests: counters.failed_requests.swap(0, Ordering::Relaxed),
+            bytes_sent: counters.bytes_sent.swap(0, Ordering::Relaxed),
+            bytes_received: counters.bytes_received.swap(0, Ordering::Relaxed),
+            latency: LatencySummary::from_histogram(&latencies.interval),
+        };
+
+        latencies.interval.reset();
+        latencies.interval_started = elapsed;
+        drop(latencies);
+
+        self.run.snapshots.lock().await.push(snapshot.clone());
+
+        snapshot
+    }
+
+    pub async fn snapshots(&self) -> Vec<Snapshot> {
+        self.run.snapshots.lock().await.clone()
+    }
+
+    pub async fn last_snapshot(&self) -> Option<Snapshot> {
+        self.run.snapshots.lock().await.last().cloned()
     }
 
     pub async fn min_latency(&self) -> u64 {
-        self.min_latency.load(Ordering::Relaxed)
+        match self.run.min_latency.load(Ordering::Relaxed) {
+            u64::MAX => 0,
+            latency => latency,
+        }
     }
 
     pub async fn max_latency(&self) -> u64 {
-        self.max_latency.load(Ordering::Relaxed)
+        self.run.max_latency.load(Ordering::Relaxed)
+    }
+
+    /// Durations too long for a `u64` are recorded as the longest one rather
+    /// than wrapping around to a short one.
+    pub fn micros(duration: Duration) -> u64 {
+        duration.as_micros().try_into().unwrap_or(u64::MAX)
     }
 
-    pub fn format_micros(&self, us: u64) -> String {
+    pub fn format_micros(us: u64) -> String {
         const MICROS_PER_MS: u64 = 1_000;
         const MICROS_PER_SEC: u64 = 1_000_000;
         const MICROS_PER_MIN: u64 = 60 * MICROS_PER_SEC;
@@ -136,7 +552,7 @@ impl Metrics {
     pub fn human_readable_bytes(&self, bytes: f64) -> String {
         const UNITS: [&str; 7] = ["B", "KB", "MB", "GB", "TB", "PB", "EB"];
 
-        let mut size = bytes as f64;
+        let mut size = bytes;
         let mut unit = 0;
 
         while size >= 1024.0 && unit < UNITS.len() - 1 {
@@ -1 +1,5 @@
+#[allow(clippy::module_inception)]
 pub mod metrics;
+pub mod snapshot;
+pub mod tagged;
+pub mod timing;
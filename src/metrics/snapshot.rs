@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use serde::Serialize;
+
+/// Counters and latency distribution of a single collection interval.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Offset of the end of the interval from the start of the test
+    pub elapsed: Duration,
+    /// Length of the interval, the last one of a test is usually shorter
+    pub interval: Duration,
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub latency: LatencySummary,
+}
+
+impl Snapshot {
+    /// Requests per second of the interval, 0 for an interval of zero length.
+    pub fn rps(&self) -> f64 {
+        if self.interval.is_zero() {
+            return 0.0;
+        }
+        self.requests as f64 / self.interval.as_secs_f64()
+    }
+}
+
+/// Percentiles of an interval histogram. Only the summary is kept so that
+/// long tests do not hold one full histogram per interval.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LatencySummary {
+    pub min: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl LatencySummary {
+    pub fn from_histogram(hist: &Histogram<u64>) -> Self {
+        if hist.is_empty() {
+            return Self::default();
+        }
+
+        Self {
+            min: hist.min(),
+            p50: hist.value_at_quantile(0.50),
+            p90: hist.value_at_quantile(0.90),
+            p95: hist.value_at_quantile(0.95),
+            p99: hist.value_at_quantile(0.99),
+            max: hist.max(),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use hdrhistogram::Histogram;
+
+use crate::metrics::metrics::{Metrics, RequestStats};
+
+/// Tags of a request such as its name, protocol, method and URL template.
+pub type Tags = BTreeMap<String, String>;
+
+/// Stats of a single request of the scenario, with the handle its requester
+/// records into the metrics of the whole run and these stats with.
+#[derive(Clone)]
+pub struct TaggedMetrics {
+    pub tags: Tags,
+    pub stats: Arc<RequestStats>,
+    pub metrics: Arc<Metrics>,
+}
+
+/// Results of the requests that share the value of a tag.
+#[derive(Debug, Clone)]
+pub struct TagGroup {
+    pub requests: u64,
+    pub failed: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub hist: Histogram<u64>,
+}
+
+impl TaggedMetrics {
+    pub fn new(tags: Tags, parent: &Arc<Metrics>) -> Self {
+        let stats = Arc::new(RequestStats::new());
+        TaggedMetrics {
+            tags,
+            metrics: Arc::new(parent.for_request(Arc::clone(&stats))),
+            stats,
+        }
+    }
+}
+
+/// Merges the metrics of the requests by the value of the `key` tag. Requests
+/// without the tag are grouped under `-`.
+pub async fn group_by(tagged: &[TaggedMetrics], key: &str) -> BTreeMap<String, TagGroup> {
+    let mut groups: BTreeMap<String, TagGroup> = BTreeMap::new();
+
+    for entry in tagged {
+        let value = entry.tags.get(key).map_or("-", String::as_str);
+        let group = groups.entry(value.to_string()).or_insert_with(|| TagGroup {
+            requests: 0,
+            failed: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            hist: Histogram::<u64>::new(3).unwrap(),
+        });
+
+        let stats = &entry.stats;
+        group.requests += stats.count().await;
+        group.failed += stats.failed().await;
+        group.bytes_sent += stats.bytes_sent().await;
+        group.bytes_received += stats.bytes_received().await;
+        group.hist.add(stats.histogram().await).unwrap();
+    }
+
+    groups
+}
@@ -0,0 +1,51 @@
+/// Phase of a request that gets its own latency histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Timing {
+    /// Resolving the host name of a new connection
+    Dns,
+    /// Establishing the TCP connection of a new connection
+    Connect,
+    /// TLS handshake of a new connection
+    Tls,
+    /// From sending the request until the response headers arrived
+    FirstByte,
+    /// Reading the response body after the headers arrived
+    Download,
+    /// From starting a gRPC call until the first message of its response
+    /// stream arrived
+    FirstMessage,
+    /// Between two consecutive messages of a gRPC response stream
+    Message,
+    /// Whole gRPC streaming call, from starting it until the stream ended
+    Stream,
+}
+
+impl Timing {
+    /// Identifier used in machine-readable reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Timing::Dns => "dns",
+            Timing::Connect => "connect",
+            Timing::Tls => "tls",
+            Timing::FirstByte => "first_byte",
+            Timing::Download => "download",
+            Timing::FirstMessage => "first_message",
+            Timing::Message => "message",
+            Timing::Stream => "stream",
+        }
+    }
+
+    /// Label used in the text report.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Timing::Dns => "DNS",
+            Timing::Connect => "TCP connect",
+            Timing::Tls => "TLS handshake",
+            Timing::FirstByte => "First byte",
+            Timing::Download => "Download",
+            Timing::FirstMessage => "First message",
+            Timing::Message => "Between messages",
+            Timing::Stream => "Stream duration",
+        }
+    }
+}
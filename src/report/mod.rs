@@ -0,0 +1,3 @@
+pub mod output;
+pub mod summary;
+pub mod threshold;
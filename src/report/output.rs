@@ -0,0 +1,71 @@
+use std::{fs::File, io, io::Write, path::PathBuf, str::FromStr};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+/// Additional report destination parsed from `FORMAT=PATH`, where `-` (or a
+/// missing path) stands for stdout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub format: OutputFormat,
+    pub target: OutputTarget,
+}
+
+impl Output {
+    pub fn is_stdout(&self) -> bool {
+        self.target == OutputTarget::Stdout
+    }
+
+    pub fn write(&self, report: &impl Serialize) -> io::Result<()> {
+        match &self.target {
+            OutputTarget::Stdout => {
+                let mut stdout = io::stdout().lock();
+                self.write_to(&mut stdout, report)
+            }
+            OutputTarget::File(path) => {
+                let mut file = File::create(path)?;
+                self.write_to(&mut file, report)
+            }
+        }
+    }
+
+    fn write_to(&self, writer: &mut impl Write, report: &impl Serialize) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *writer, report)?;
+                writeln!(writer)
+            }
+        }
+    }
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, target) = s.split_once('=').unwrap_or((s, "-"));
+
+        let format = match format.trim() {
+            "json" => OutputFormat::Json,
+            other => return Err(format!("unsupported output format '{}'", other)),
+        };
+
+        let target = match target.trim() {
+            "-" => OutputTarget::Stdout,
+            "" => return Err(format!("missing output path in '{}'", s)),
+            path => OutputTarget::File(PathBuf::from(path)),
+        };
+
+        Ok(Output { format, target })
+    }
+}
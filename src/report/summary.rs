@@ -0,0 +1,427 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use hdrhistogram::Histogram;
+use serde::Serialize;
+
+use crate::{
+    metrics::{
+        metrics::Metrics,
+        snapshot::LatencySummary,
+        tagged::{self, TaggedMetrics, Tags},
+    },
+    report::threshold::ThresholdResult,
+    requester::params::RequestParams,
+    scenario::scenario::ScenarioRequest,
+    scheduler::scheduler::{Executor, SchedulerConfig},
+};
+
+/// Machine-readable result of a run, serialised by the JSON output.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub config: RunConfig,
+    pub duration_secs: f64,
+    pub requests: RequestSummary,
+    pub data: DataSummary,
+    /// gRPC messages, one per unary call and every message of a stream
+    pub messages: MessageSummary,
+    /// TCP connections opened by HTTP, gRPC and WebSocket requests
+    pub connections_opened: u64,
+    pub latency_us: LatencyPercentiles,
+    /// Latency measured from the intended send time, only when a rate is set
+    pub corrected_latency_us: Option<LatencyPercentiles>,
+    /// Per-phase latencies, connection phases only cover new connections
+    pub timings_us: BTreeMap<&'static str, TimingSummary>,
+    pub status_codes: BTreeMap<u16, u64>,
+    /// Responses by the HTTP version negotiated for their connection
+    pub http_versions: BTreeMap<&'static str, u64>,
+    pub grpc_codes: BTreeMap<String, u64>,
+    pub errors: BTreeMap<String, ErrorSummary>,
+    /// Passed and failed responses of every check by its expression
+    pub checks: BTreeMap<String, CheckSummary>,
+    /// Results of every request of the traffic mix by name
+    pub named_requests: BTreeMap<String, NamedRequestSummary>,
+    /// Results of every request of the scenario with its tags
+    pub tagged: Vec<TaggedSummary>,
+    pub timeline: Vec<TimelineEntry>,
+    /// Filled in once thresholds were evaluated against this summary
+    pub thresholds: Vec<ThresholdResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunConfig {
+    pub targets: Vec<TargetConfig>,
+    pub executor: Executor,
+    pub concurrency: u64,
+    pub duration_secs: u64,
+    pub rate: Option<u64>,
+    pub max_in_flight: Option<u64>,
+    pub timeout_secs: u64,
+    pub stages: Option<Vec<StageConfig>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TargetConfig {
+    pub name: String,
+    pub weight: u32,
+    pub protocol: &'static str,
+    pub url: String,
+    pub method: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StageConfig {
+    pub duration_secs: f64,
+    pub target: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequestSummary {
+    pub total: u64,
+    pub successful: u64,
+    pub failed: u64,
+    pub dropped_iterations: u64,
+    pub rps: f64,
+    pub success_rate: f64,
+    pub error_rate: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DataSummary {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub bytes_sent_per_sec: f64,
+    pub bytes_received_per_sec: f64,
+    /// Size of compressed gRPC messages, the other counts are uncompressed
+    pub compressed_bytes_sent: u64,
+    pub compressed_bytes_received: u64,
+    /// Uncompressed size of the compressed gRPC messages
+    pub uncompressed_bytes_sent: u64,
+    pub uncompressed_bytes_received: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MessageSummary {
+    pub sent: u64,
+    pub received: u64,
+    pub sent_per_sec: f64,
+    pub received_per_sec: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencyPercentiles {
+    pub min: u64,
+    pub mean: f64,
+    pub stdev: f64,
+    pub p50: u64,
+    pub p75: u64,
+    pub p90: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub p99_9: u64,
+    pub p99_99: u64,
+    pub max: u64,
+}
+
+impl LatencyPercentiles {
+    fn from_histogram(hist: &Histogram<u64>) -> Self {
+        Self {
+            min: hist.min(),
+            mean: hist.mean(),
+            stdev: hist.stdev(),
+            p50: hist.value_at_quantile(0.50),
+            p75: hist.value_at_quantile(0.75),
+            p90: hist.value_at_quantile(0.90),
+            p95: hist.value_at_quantile(0.95),
+            p99: hist.value_at_quantile(0.99),
+            p99_9: hist.value_at_quantile(0.999),
+            p99_99: hist.value_at_quantile(0.9999),
+            max: hist.max(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimingSummary {
+    pub count: u64,
+    #[serde(flatten)]
+    pub latency: LatencyPercentiles,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckSummary {
+    pub passed: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorSummary {
+    pub count: u64,
+    /// First message seen for this kind of error
+    pub example: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamedRequestSummary {
+    pub total: u64,
+    pub failed: u64,
+    pub rps: f64,
+    pub error_rate: f64,
+    pub latency_us: LatencyPercentiles,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaggedSummary {
+    pub tags: Tags,
+    pub total: u64,
+    pub failed: u64,
+    pub rps: f64,
+    pub error_rate: f64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub latency_us: LatencyPercentiles,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimelineEntry {
+    pub elapsed_secs: f64,
+    pub interval_secs: f64,
+    pub requests: u64,
+    pub failed_requests: u64,
+    pub rps: f64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub latency_us: LatencySummary,
+}
+
+impl Summary {
+    pub async fn collect(
+        metrics: &Metrics,
+        config: &SchedulerConfig,
+        requests: &[ScenarioRequest],
+        tagged: &[TaggedMetrics],
+        elapsed: Duration,
+    ) -> Self {
+        let elapsed = elapsed.as_secs_f64();
+
+        let total = metrics.total_requests().await;
+        let successful = metrics.successful_requests().await;
+        let failed = metrics.failed_requests().await;
+        let ratio = |count: u64| {
+            if total > 0 {
+                count as f64 / total as f64 * 100.0
+            } else {
+                0.0
+            }
+        };
+
+        let bytes_sent = metrics.bytes_sent().await;
+        let messages_sent = metrics.messages_sent().await;
+        let messages_received = metrics.messages_received().await;
+        let bytes_received = metrics.bytes_received().await;
+
+        let paced = config.rate.is_some() || config.executor == Executor::Open;
+
+        Summary {
+            config: RunConfig::new(config, requests),
+            duration_secs: elapsed,
+            requests: RequestSummary {
+                total,
+                successful,
+                failed,
+                dropped_iterations: metrics.dropped_iterations().await,
+                rps: total as f64 / elapsed,
+                success_rate: ratio(successful),
+                error_rate: ratio(failed),
+            },
+            data: DataSummary {
+                bytes_sent,
+                bytes_received,
+                bytes_sent_per_sec: bytes_sent as f64 / elapsed,
+                bytes_received_per_sec: bytes_received as f64 / elapsed,
+                compressed_bytes_sent: metrics.compressed_bytes_sent().await,
+                compressed_bytes_received: metrics.compressed_bytes_received().await,
+                uncompressed_bytes_sent: metrics.uncompressed_bytes_sent().await,
+                uncompressed_bytes_received: metrics.uncompressed_bytes_received().await,
+            },
+            messages: MessageSummary {
+                sent: messages_sent,
+                received: messages_received,
+                sent_per_sec: messages_sent as f64 / elapsed,
+                received_per_sec: messages_received as f64 / elapsed,
+            },
+            connections_opened: metrics.connections_opened().await,
+            latency_us: LatencyPercentiles::from_histogram(&metrics.histogram().await),
+            corrected_latency_us: if paced {
+                Some(LatencyPercentiles::from_histogram(
+                    &metrics.corrected_histogram().await,
+                ))
+            } else {
+                None
+            },
+            timings_us: metrics
+                .timings()
+                .await
+                .into_iter()
+                .map(|(timing, hist)| {
+                    (
+                        timing.name(),
+                        TimingSummary {
+                            count: hist.len(),
+                            latency: LatencyPercentiles::from_histogram(&hist),
+                        },
+                    )
+                })
+                .collect(),
+            status_codes: metrics.http_statuses().await,
+            http_versions: metrics.http_versions().await,
+            grpc_codes: metrics
+                .grpc_codes()
+                .await
+                .into_iter()
+                .map(|(code, count)| (format!("{:?}", code), count))
+                .collect(),
+            errors: metrics
+                .errors()
+                .await
+                .into_iter()
+                .map(|(kind, stats)| {
+                    (
+                        kind.to_string(),
+                        ErrorSummary {
+                            count: stats.count,
+                            example: stats.example,
+                        },
+                    )
+                })
+                .collect(),
+            checks: metrics
+                .checks()
+                .await
+                .into_iter()
+                .map(|(name, stats)| {
+                    (
+                        name,
+                        CheckSummary {
+                            passed: stats.passed,
+                            failed: stats.failed,
+                        },
+                    )
+                })
+                .collect(),
+            named_requests: tagged::group_by(tagged, "name")
+                .await
+                .into_iter()
+                .map(|(name, group)| {
+                    (
+                        name,
+                        NamedRequestSummary {
+                            total: group.requests,
+                            failed: group.failed,
+                            rps: group.requests as f64 / elapsed,
+                            error_rate: if group.requests > 0 {
+                                group.failed as f64 / group.requests as f64 * 100.0
+                            } else {
+                                0.0
+                            },
+                            latency_us: LatencyPercentiles::from_histogram(&group.hist),
+                        },
+                    )
+                })
+                .collect(),
+            tagged: {
+                let mut tagged_summaries = Vec::new();
+                for entry in tagged {
+                    let stats = &entry.stats;
+                    let total = stats.count().await;
+                    let failed = stats.failed().await;
+
+                    tagged_summaries.push(TaggedSummary {
+                        tags: entry.tags.clone(),
+                        total,
+                        failed,
+                        rps: total as f64 / elapsed,
+                        error_rate: if total > 0 {
+                            failed as f64 / total as f64 * 100.0
+                        } else {
+                            0.0
+                        },
+                        bytes_sent: stats.bytes_sent().await,
+                        bytes_received: stats.bytes_received().await,
+                        latency_us: LatencyPercentiles::from_histogram(&stats.histogram().await),
+                    });
+                }
+                tagged_summaries
+            },
+            timeline: metrics
+                .snapshots()
+                .await
+                .into_iter()
+                .map(|snapshot| TimelineEntry {
+                    elapsed_secs: snapshot.elapsed.as_secs_f64(),
+                    interval_secs: snapshot.interval.as_secs_f64(),
+                    requests: snapshot.requests,
+                    failed_requests: snapshot.failed_requests,
+                    rps: snapshot.rps(),
+                    bytes_sent: snapshot.bytes_sent,
+                    bytes_received: snapshot.bytes_received,
+                    latency_us: snapshot.latency,
+                })
+                .collect(),
+            thresholds: Vec::new(),
+        }
+    }
+}
+
+impl RunConfig {
+    fn new(config: &SchedulerConfig, requests: &[ScenarioRequest]) -> Self {
+        RunConfig {
+            targets: requests.iter().map(TargetConfig::new).collect(),
+            executor: config.executor,
+            concurrency: config.concurrency,
+            duration_secs: config.duration,
+            rate: config.rate,
+            max_in_flight: (config.executor == Executor::Open).then_some(config.max_in_flight),
+            timeout_secs: config.timeout,
+            stages: config.stages.as_ref().map(|stages| {
+                stages
+                    .iter()
+                    .map(|stage| StageConfig {
+                        duration_secs: stage.duration.as_secs_f64(),
+                        target: stage.target,
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl TargetConfig {
+    fn new(request: &ScenarioRequest) -> Self {
+        let name = request.name.clone();
+        let weight = request.weight;
+
+        match &request.params {
+            RequestParams::Http(params) => TargetConfig {
+                name,
+                weight,
+                protocol: "http",
+                url: params.url.clone(),
+                method: Some(params.method.to_string()),
+            },
+            RequestParams::Grpc(params) => TargetConfig {
+                name,
+                weight,
+                protocol: "grpc",
+                url: params.address.clone(),
+                method: Some(params.method.clone()),
+            },
+            RequestParams::Websocket(params) => TargetConfig {
+                name,
+                weight,
+                protocol: "websocket",
+                url: params.url.clone(),
+                method: None,
+            },
+        }
+    }
+}
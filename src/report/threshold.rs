@@ -0,0 +1,329 @@
+use std::{fmt, str::FromStr};
+
+use hdrhistogram::Histogram;
+use serde::Serialize;
+
+use crate::{metrics::metrics::Metrics, report::summary::Summary};
+
+/// Exit code used when at least one threshold is not met.
+pub const THRESHOLDS_FAILED_EXIT_CODE: i32 = 99;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    /// Latency percentile between 0 and 100
+    Percentile(f64),
+    MinLatency,
+    MeanLatency,
+    MaxLatency,
+    ErrorRate,
+    SuccessRate,
+    Rps,
+    Requests,
+    Failed,
+    Dropped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    Micros,
+    Percent,
+    Number,
+}
+
+impl Metric {
+    fn unit(&self) -> Unit {
+        match self {
+            Metric::Percentile(_)
+            | Metric::MinLatency
+            | Metric::MeanLatency
+            | Metric::MaxLatency => Unit::Micros,
+            Metric::ErrorRate | Metric::SuccessRate => Unit::Percent,
+            Metric::Rps | Metric::Requests | Metric::Failed | Metric::Dropped => Unit::Number,
+        }
+    }
+
+    fn value(&self, summary: &Summary, histogram: &Histogram<u64>) -> f64 {
+        match self {
+            Metric::Percentile(p) => histogram.value_at_quantile(p / 100.0) as f64,
+            Metric::MinLatency => summary.latency_us.min as f64,
+            Metric::MeanLatency => summary.latency_us.mean,
+            Metric::MaxLatency => summary.latency_us.max as f64,
+            Metric::ErrorRate => summary.requests.error_rate,
+            Metric::SuccessRate => summary.requests.success_rate,
+            Metric::Rps => summary.requests.rps,
+            Metric::Requests => summary.requests.total as f64,
+            Metric::Failed => summary.requests.failed as f64,
+            Metric::Dropped => summary.requests.dropped_iterations as f64,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "min" => Ok(Metric::MinLatency),
+            "avg" | "mean" => Ok(Metric::MeanLatency),
+            "max" => Ok(Metric::MaxLatency),
+            "error_rate" => Ok(Metric::ErrorRate),
+            "success_rate" => Ok(Metric::SuccessRate),
+            "rps" => Ok(Metric::Rps),
+            "requests" => Ok(Metric::Requests),
+            "failed" => Ok(Metric::Failed),
+            "dropped" => Ok(Metric::Dropped),
+            other => {
+                let percentile = other
+                    .strip_prefix('p')
+                    .and_then(|p| p.parse::<f64>().ok())
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or_else(|| format!("unknown threshold metric '{}'", other))?;
+                Ok(Metric::Percentile(percentile))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+impl Operator {
+    fn compare(&self, value: f64, limit: f64) -> bool {
+        match self {
+            Operator::Less => value < limit,
+            Operator::LessOrEqual => value <= limit,
+            Operator::Greater => value > limit,
+            Operator::GreaterOrEqual => value >= limit,
+            Operator::Equal => value == limit,
+        }
+    }
+}
+
+/// Pass/fail condition on the final metrics such as `p99<200ms`,
+/// `error_rate<0.1%` or `rps>5000`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    expression: String,
+    metric: Metric,
+    operator: Operator,
+    limit: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThresholdResult {
+    pub expression: String,
+    pub value: f64,
+    pub passed: bool,
+}
+
+impl Threshold {
+    pub fn evaluate(&self, summary: &Summary, histogram: &Histogram<u64>) -> ThresholdResult {
+        let value = self.metric.value(summary, histogram);
+
+        ThresholdResult {
+            expression: self.expression.clone(),
+            value,
+            passed: self.operator.compare(value, self.limit),
+        }
+    }
+
+    /// Formats a value of this threshold's metric for the text report.
+    pub fn format_value(&self, value: f64) -> String {
+        match self.metric.unit() {
+            Unit::Micros => Metrics::format_micros(value as u64),
+            Unit::Percent => format!("{:.2}%", value),
+            Unit::Number => format!("{:.2}", value),
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+
+        let position = expression
+            .find(['<', '>', '='])
+            .ok_or_else(|| format!("missing comparison operator in threshold '{}'", s))?;
+        let (metric, rest) = expression.split_at(position);
+
+        let (operator, limit) = if let Some(limit) = rest.strip_prefix("<=") {
+            (Operator::LessOrEqual, limit)
+        } else if let Some(limit) = rest.strip_prefix(">=") {
+            (Operator::GreaterOrEqual, limit)
+        } else if let Some(limit) = rest.strip_prefix("==") {
+            (Operator::Equal, limit)
+        } else if let Some(limit) = rest.strip_prefix('<') {
+            (Operator::Less, limit)
+        } else if let Some(limit) = rest.strip_prefix('>') {
+            (Operator::Greater, limit)
+        } else {
+            return Err(format!("invalid comparison operator in threshold '{}'", s));
+        };
+
+        let metric = Metric::from_str(metric)?;
+        let limit = parse_limit(metric.unit(), limit)
+            .map_err(|e| format!("invalid value in threshold '{}': {}", s, e))?;
+
+        Ok(Threshold {
+            expression,
+            metric,
+            operator,
+            limit,
+        })
+    }
+}
+
+fn parse_limit(unit: Unit, limit: &str) -> Result<f64, String> {
+    match unit {
+        // A bare number is taken as milliseconds
+        Unit::Micros => match limit.parse::<f64>() {
+            Ok(millis) => Ok(millis * 1000.0),
+            Err(_) => humantime::parse_duration(limit)
+                .map(|duration| duration.as_micros() as f64)
+                .map_err(|e| e.to_string()),
+        },
+        Unit::Percent => limit
+            .strip_suffix('%')
+            .unwrap_or(limit)
+            .parse::<f64>()
+            .map_err(|e| e.to_string()),
+        Unit::Number => limit.parse::<f64>().map_err(|e| e.to_string()),
+    }
+}
+
+/// Prints one line per threshold with its outcome and the measured value.
+pub fn print_results(thresholds: &[Threshold], results: &[ThresholdResult]) {
+    if thresholds.is_empty() {
+        return;
+    }
+
+    println!("Thresholds:");
+    for (threshold, result) in thresholds.iter().zip(results) {
+        let label = format!("   {}:", threshold);
+        println!(
+            "{:.<32}{} ({})",
+            label,
+            if result.passed { "passed" } else { "FAILED" },
+            threshold.format_value(result.value)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_thresholds() {
+        let cases = [
+            (
+                "p99<200ms",
+                Metric::Percentile(99.0),
+                Operator::Less,
+                200_000.0,
+            ),
+            (
+                "p99.9 <= 1s",
+                Metric::Percentile(99.9),
+                Operator::LessOrEqual,
+                1_000_000.0,
+            ),
+            (
+                "p50<250",
+                Metric::Percentile(50.0),
+                Operator::Less,
+                250_000.0,
+            ),
+            ("avg<1.5", Metric::MeanLatency, Operator::Less, 1_500.0),
+            ("max<500us", Metric::MaxLatency, Operator::Less, 500.0),
+            ("error_rate<0.1%", Metric::ErrorRate, Operator::Less, 0.1),
+            (
+                "success_rate>=99",
+                Metric::SuccessRate,
+                Operator::GreaterOrEqual,
+                99.0,
+            ),
+            ("rps>5000", Metric::Rps, Operator::Greater, 5000.0),
+            ("failed==0", Metric::Failed, Operator::Equal, 0.0),
+            ("dropped<=10", Metric::Dropped, Operator::LessOrEqual, 10.0),
+        ];
+
+        for (input, metric, operator, limit) in cases {
+            let threshold: Threshold = input.parse().unwrap();
+            assert_eq!(threshold.metric, metric, "{}", input);
+            assert_eq!(threshold.operator, operator, "{}", input);
+            assert!((threshold.limit - limit).abs() < 1e-9, "{}", input);
+            assert_eq!(threshold.expression, input.replace(' ', ""));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_thresholds() {
+        let inputs = [
+            "p99",
+            "p99!200ms",
+            "p101<200ms",
+            "latency<200ms",
+            "p99<fast",
+            "rps>many",
+            "error_rate<%",
+        ];
+
+        for input in inputs {
+            assert!(input.parse::<Threshold>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn compares_values() {
+        let cases = [
+            (Operator::Less, 1.0, 2.0, true),
+            (Operator::Less, 2.0, 2.0, false),
+            (Operator::LessOrEqual, 2.0, 2.0, true),
+            (Operator::Greater, 3.0, 2.0, true),
+            (Operator::Greater, 2.0, 2.0, false),
+            (Operator::GreaterOrEqual, 2.0, 2.0, true),
+            (Operator::Equal, 2.0, 2.0, true),
+            (Operator::Equal, 2.5, 2.0, false),
+        ];
+
+        for (operator, value, limit, expected) in cases {
+            assert_eq!(
+                operator.compare(value, limit),
+                expected,
+                "{:?} {} {}",
+                operator,
+                value,
+                limit
+            );
+        }
+    }
+
+    #[test]
+    fn formats_values_in_the_unit_of_the_metric() {
+        let cases = [
+            ("p99<200ms", 150_000.0, "150ms"),
+            ("error_rate<1%", 0.5, "0.50%"),
+            ("rps>100", 123.456, "123.46"),
+        ];
+
+        for (input, value, expected) in cases {
+            let threshold: Threshold = input.parse().unwrap();
+            assert_eq!(threshold.format_value(value), expected, "{}", input);
+        }
+    }
+}
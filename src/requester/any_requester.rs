@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use crate::metrics::metrics::Metrics;
+use crate::requester::client::{ClientOptions, RequestClients};
+use crate::requester::error::RequestError;
+use crate::requester::grpc_requester::GrpcRequester;
+use crate::requester::http_requester::HttpRequester;
+use crate::requester::params::RequestParams;
+use crate::requester::websocket_requester::WebsocketRequester;
+use crate::requester::Requester;
+use crate::template::context::RequestContext;
+
+/// Requester for any of the supported protocols, built from `RequestParams`.
+pub enum AnyRequester<'a> {
+    Http(Box<HttpRequester<'a>>),
+    Grpc(Box<GrpcRequester<'a>>),
+    Websocket(Box<WebsocketRequester<'a>>),
+}
+
+impl<'a> AnyRequester<'a> {
+    /// `clients` are the clients of the request shared by the workers, of
+    /// which the worker `worker_id` uses one.
+    pub fn new(
+        metrics: &'a Arc<Metrics>,
+        request_params: RequestParams,
+        options: &ClientOptions,
+        clients: &RequestClients,
+        worker_id: u64,
+    ) -> Self {
+        match request_params {
+            RequestParams::Http(params) => AnyRequester::Http(Box::new(
+                HttpRequester::new(
+                    metrics,
+                    params.method,
+                    params.url,
+                    params.body,
+                    params.form,
+                    params.headers,
+                    options.timeout,
+                )
+                .with_checks(params.checks)
+                .with_extractors(params.extract)
+                .with_tls(Arc::clone(&options.tls))
+                .with_proxy(Arc::clone(&options.proxy))
+                .with_connection(options.connection.clone())
+                .with_templates(options.templates)
+                .with_shared_client(clients.http.slot(worker_id)),
+            )),
+            RequestParams::Grpc(params) => AnyRequester::Grpc(Box::new(
+                GrpcRequester::new(
+                    metrics,
+                    params.address,
+                    params.protos,
+                    params.method,
+                    params.data,
+                    options.timeout,
+                )
+                .with_messages(params.messages)
+                .with_metadata(params.metadata)
+                .with_deadline(params.deadline)
+                .with_compression(params.compression)
+                .with_templates(options.templates)
+                .with_checks(params.checks)
+                .with_tls(Arc::clone(&options.tls))
+                .with_descriptors(Arc::clone(&clients.grpc_descriptors))
+                .with_shared_channel(clients.grpc.slot(worker_id)),
+            )),
+            RequestParams::Websocket(params) => AnyRequester::Websocket(Box::new(
+                WebsocketRequester::new(metrics, params.url, params.data)
+                    .with_tls(Arc::clone(&options.tls))
+                    .with_templates(options.templates),
+            )),
+        }
+    }
+}
+
+impl Requester for AnyRequester<'_> {
+    async fn initialize(&self) -> Result<(), RequestError> {
+        match self {
+            AnyRequester::Http(requester) => requester.initialize().await,
+            AnyRequester::Grpc(requester) => requester.initialize().await,
+            AnyRequester::Websocket(requester) => requester.initialize().await,
+        }
+    }
+
+    async fn request(&self, context: &mut RequestContext) -> Result<(), RequestError> {
+        match self {
+            AnyRequester::Http(requester) => requester.request(context).await,
+            AnyRequester::Grpc(requester) => requester.request(context).await,
+            AnyRequester::Websocket(requester) => requester.request(context).await,
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::{fmt, str::FromStr};
+
+use http::{header::HeaderName, HeaderMap};
+use regex::Regex;
+
+/// Assertion on a response such as `status:200,201`, `body-contains:"ok"` or
+/// `json:$.status=active`, or `code:OK,NOT_FOUND` for gRPC. A request fails
+/// when one of its checks fails.
+#[derive(Debug, Clone)]
+pub struct Check {
+    expression: String,
+    kind: CheckKind,
+}
+
+#[derive(Debug, Clone)]
+enum CheckKind {
+    /// Status is one of the expected ones
+    Status(Vec<u16>),
+    /// gRPC status code is one of the expected ones
+    Code(Vec<tonic::Code>),
+    /// Header is present and, when a value is given, has that value
+    Header(HeaderName, Option<String>),
+    BodyContains(String),
+    BodyRegex(Regex),
+    /// Value at a JSONPath of the body equals the expected JSON value
+    Json(JsonPath, serde_json::Value),
+    /// Body is at most this many bytes
+    MaxBodySize(usize),
+}
+
+/// JSONPath made of keys and indexes, such as `$.items[0].id`.
+#[derive(Debug, Clone)]
+pub struct JsonPath(Vec<PathSegment>);
+
+#[derive(Debug, Clone)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// The parts of a response that checks look at.
+pub struct Response<'a> {
+    pub status: u16,
+    pub headers: &'a HeaderMap,
+    pub body: &'a [u8],
+}
+
+/// The parts of a gRPC response that checks look at. Headers are the
+/// response metadata, the body is the JSON of the response message, or an
+/// array of the messages of a response stream.
+pub struct GrpcResponse<'a> {
+    pub code: tonic::Code,
+    pub metadata: &'a HeaderMap,
+    /// `None` when the call failed
+    pub message: Option<&'a serde_json::Value>,
+    /// Encoded size of the response messages
+    pub size: usize,
+}
+
+impl Check {
+    /// The expression the check was parsed from, used to name it in reports.
+    pub fn name(&self) -> &str {
+        &self.expression
+    }
+
+    /// Status checks replace the default rule that 4xx and 5xx responses fail.
+    pub fn is_status(&self) -> bool {
+        matches!(self.kind, CheckKind::Status(_))
+    }
+
+    /// Parses a check of HTTP responses, which cannot expect a gRPC code.
+    pub fn parse_http(s: &str) -> Result<Self, String> {
+        let check = Check::from_str(s)?;
+        if check.is_code() {
+            return Err(format!(
+                "check '{}' applies to gRPC requests, use status: for HTTP",
+                s
+            ));
+        }
+        Ok(check)
+    }
+
+    /// Parses a check of gRPC responses, which cannot expect an HTTP status.
+    pub fn parse_grpc(s: &str) -> Result<Self, String> {
+        let check = Check::from_str(s)?;
+        if check.is_status() {
+            return Err(format!(
+                "check '{}' applies to HTTP requests, use code: for gRPC",
+                s
+            ));
+        }
+        Ok(check)
+    }
+
+    /// Code checks replace the default rule that calls not ending with `OK`
+    /// fail.
+    pub fn is_code(&self) -> bool {
+        matches!(self.kind, CheckKind::Code(_))
+    }
+
+    pub fn evaluate(&self, response: &Response) -> bool {
+        match &self.kind {
+            CheckKind::Status(statuses) => statuses.contains(&response.status),
+            CheckKind::Code(_) => false,
+            CheckKind::Header(name, expected) => match response.headers.get(name) {
+                Some(value) => expected
+                    .as_ref()
+                    .is_none_or(|expected| value.as_bytes() == expected.as_bytes()),
+                None => false,
+            },
+            CheckKind::BodyContains(text) => String::from_utf8_lossy(response.body).contains(text),
+            CheckKind::BodyRegex(regex) => regex.is_match(&String::from_utf8_lossy(response.body)),
+            CheckKind::Json(path, expected) => serde_json::from_slice(response.body)
+                .ok()
+                .and_then(|body: serde_json::Value| path.lookup(&body).cloned())
+                .is_some_and(|value| &value == expected),
+            CheckKind::MaxBodySize(size) => response.body.len() <= *size,
+        }
+    }
+
+    pub fn evaluate_grpc(&self, response: &GrpcResponse) -> bool {
+        let text = || response.message.map(|message| message.to_string());
+
+        match &self.kind {
+            CheckKind::Status(_) => false,
+            CheckKind::Code(codes) => codes.contains(&response.code),
+            CheckKind::Header(name, expected) => match response.metadata.get(name) {
+                Some(value) => expected
+                    .as_ref()
+                    .is_none_or(|expected| value.as_bytes() == expected.as_bytes()),
+                None => false,
+            },
+            CheckKind::BodyContains(expected) => text().is_some_and(|text| text.contains(expected)),
+            CheckKind::BodyRegex(regex) => text().is_some_and(|text| regex.is_match(&text)),
+            CheckKind::Json(path, expected) => response
+                .message
+                .and_then(|message| path.lookup(message))
+                .is_some_and(|value| value == expected),
+            CheckKind::MaxBodySize(size) => response.size <= *size,
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, argument) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid check '{}': expected KIND:ARGUMENT", s))?;
+
+        let kind = match kind.trim() {
+            "status" => CheckKind::Status(
+                argument
+                    .split(',')
+                    .map(|status| {
+                        status
+                            .trim()
+                            .parse::<u16>()
+                            .map_err(|_| format!("invalid status '{}' in check '{}'", status, s))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            "code" => CheckKind::Code(
+                argument
+                    .split(',')
+                    .map(|code| {
+                        parse_code(code.trim())
+                            .ok_or_else(|| format!("invalid gRPC code '{}' in check '{}'", code, s))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            "header" => {
+                let (name, value) = match argument.split_once('=') {
+                    Some((name, value)) => (name, Some(value.trim().to_string())),
+                    None => (argument, None),
+                };
+                let name = HeaderName::from_bytes(name.trim().as_bytes())
+                    .map_err(|_| format!("invalid header name '{}' in check '{}'", name, s))?;
+                CheckKind::Header(name, value)
+            }
+            "body-contains" => CheckKind::BodyContains(argument.to_string()),
+            "body-regex" => CheckKind::BodyRegex(
+                Regex::new(argument)
+                    .map_err(|e| format!("invalid regex in check '{}': {}", s, e))?,
+            ),
+            "json" => {
+                let (path, value) = argument
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid check '{}': expected json:PATH=VALUE", s))?;
+                let value = value.trim();
+                // Values that are not valid JSON are compared as strings, so
+                // that `json:$.status=active` does not need quotes
+                let value = serde_json::from_str(value)
+                    .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+                CheckKind::Json(path.trim().parse()?, value)
+            }
+            "max-body-size" => CheckKind::MaxBodySize(
+                argument
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid size '{}' in check '{}'", argument, s))?,
+            ),
+            other => return Err(format!("unknown check '{}'", other)),
+        };
+
+        Ok(Check {
+            expression: s.to_string(),
+            kind,
+        })
+    }
+}
+
+/// Parses a gRPC status code by its number or its name, such as `5`,
+/// `NOT_FOUND` or `NotFound`.
+fn parse_code(code: &str) -> Option<tonic::Code> {
+    if let Ok(number) = code.parse::<i32>() {
+        return (0..=16)
+            .contains(&number)
+            .then(|| tonic::Code::from_i32(number));
+    }
+
+    let name = code.replace('_', "").to_lowercase();
+    (0..=16)
+        .map(tonic::Code::from_i32)
+        .find(|code| format!("{:?}", code).to_lowercase() == name)
+}
+
+impl JsonPath {
+    pub fn lookup<'a>(&self, value: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                PathSegment::Key(key) => value.get(key),
+                PathSegment::Index(index) => value.get(index),
+            })
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let mut rest = path
+            .strip_prefix('$')
+            .ok_or_else(|| format!("JSONPath '{}' must start with '$'", path))?;
+        let mut segments = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(format!("empty key in JSONPath '{}'", path));
+                }
+                segments.push(PathSegment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after
+                    .find(']')
+                    .ok_or_else(|| format!("unclosed '[' in JSONPath '{}'", path))?;
+                let index = &after[..end];
+                let segment = match index.strip_prefix('\'').and_then(|i| i.strip_suffix('\'')) {
+                    Some(key) => PathSegment::Key(key.to_string()),
+                    None => PathSegment::Index(index.parse().map_err(|_| {
+                        format!("invalid index '{}' in JSONPath '{}'", index, path)
+                    })?),
+                };
+                segments.push(segment);
+                rest = &after[end + 1..];
+            } else {
+                return Err(format!("invalid JSONPath '{}'", path));
+            }
+        }
+
+        Ok(JsonPath(segments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_path_lookup() {
+        let body = json!({
+            "status": "active",
+            "items": [{"id": 7, "tags": ["a", "b"]}],
+            "odd.key": true
+        });
+
+        let cases = [
+            ("$", Some(body.clone())),
+            ("$.status", Some(json!("active"))),
+            ("$.items[0].id", Some(json!(7))),
+            ("$.items[0].tags[1]", Some(json!("b"))),
+            ("$['odd.key']", Some(json!(true))),
+            ("$.items[1]", None),
+            ("$.missing", None),
+            ("$.status[0]", None),
+        ];
+
+        for (path, expected) in cases {
+            let path: JsonPath = path.parse().unwrap();
+            assert_eq!(path.lookup(&body).cloned(), expected, "{:?}", path);
+        }
+    }
+
+    #[test]
+    fn json_path_rejects_invalid_paths() {
+        for path in ["status", "$.", "$..id", "$[0", "$[x]", "$items"] {
+            assert!(path.parse::<JsonPath>().is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn json_check_compares_values() {
+        let headers = HeaderMap::new();
+        let cases = [
+            ("json:$.status=active", r#"{"status": "active"}"#, true),
+            ("json:$.status=\"active\"", r#"{"status": "active"}"#, true),
+            ("json:$.count=3", r#"{"count": 3}"#, true),
+            ("json:$.count=3", r#"{"count": "3"}"#, false),
+            ("json:$.ok=true", r#"{"ok": true}"#, true),
+            ("json:$.items[0]=1", r#"{"items": [1, 2]}"#, true),
+            ("json:$.status=active", "not json", false),
+        ];
+
+        for (check, body, passed) in cases {
+            let response = Response {
+                status: 200,
+                headers: &headers,
+                body: body.as_bytes(),
+            };
+            let check: Check = check.parse().unwrap();
+            assert_eq!(check.evaluate(&response), passed, "{} on {}", check, body);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use clap::Args;
+use http_body_util::Full;
+use hyper_util::client::legacy::Client;
+use prost_reflect::DescriptorPool;
+use serde::Deserialize;
+use tokio::sync::{OnceCell, Semaphore, SemaphorePermit};
+use tonic::transport::Channel;
+
+use crate::requester::{connector::TimingConnector, proxy::ProxyConfig, tls::TlsConfig};
+
+pub type HttpClient = Client<TimingConnector, Full<Bytes>>;
+
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+pub enum HttpVersion {
+    /// HTTP/2 when the server selects it in the TLS handshake, HTTP/1.1 otherwise
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    /// HTTP/1.1 only
+    #[value(name = "1.1")]
+    #[serde(rename = "1.1")]
+    Http1,
+    /// HTTP/2 only, with prior knowledge (h2c) for URLs without TLS
+    #[value(name = "2", alias = "h2c")]
+    #[serde(rename = "2", alias = "h2c")]
+    Http2,
+}
+
+impl HttpVersion {
+    pub fn alpn(&self) -> &'static [&'static str] {
+        match self {
+            HttpVersion::Auto => &["h2", "http/1.1"],
+            HttpVersion::Http1 => &["http/1.1"],
+            HttpVersion::Http2 => &["h2"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConnectionReuse {
+    /// Idle connections are kept open and reused
+    #[default]
+    KeepAlive,
+    /// Every request opens a new TCP and TLS connection, closed after the response
+    New,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+pub struct ConnectionOptions {
+    #[arg(
+        long,
+        value_enum,
+        value_name = "VERSION",
+        default_value_t = HttpVersion::Auto,
+        help = "HTTP version of HTTP requests: auto negotiates HTTP/2 over TLS, 2 forces it and uses prior knowledge (h2c) without TLS"
+    )]
+    pub http_version: HttpVersion,
+
+    #[arg(
+        long,
+        value_name = "CLIENTS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Number of HTTP clients and gRPC channels the workers share, so that with HTTP/2 the requests of concurrency/CLIENTS workers are streams of one connection [default: one per worker]"
+    )]
+    pub clients: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "STREAMS",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Maximum number of concurrent requests sent with one of the shared --clients, with HTTP/2 the streams of its connection, the others wait for a free stream [default: unlimited]"
+    )]
+    pub max_concurrent_streams: Option<u32>,
+
+    #[arg(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        default_value_t = ConnectionReuse::KeepAlive,
+        help = "Reuse HTTP connections or open a new TCP and TLS connection for every request"
+    )]
+    pub connection_reuse: ConnectionReuse,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        help = "Maximum number of HTTP connections each client opens per host, requests wait for a free one [default: unlimited]"
+    )]
+    pub pool_size: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "DURATION",
+        value_parser = humantime::parse_duration,
+        help = "How long idle HTTP connections are kept open, for example 30s [default: 90s]"
+    )]
+    pub idle_timeout: Option<Duration>,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Maximum number of redirects an HTTP request follows, 0 to record redirects as responses [default: 10]"
+    )]
+    pub max_redirects: Option<usize>,
+}
+
+#[derive(Clone, Default)]
+pub struct ClientOptions {
+    /// Request timeout in seconds
+    pub timeout: u64,
+    pub tls: Arc<TlsConfig>,
+    pub proxy: Arc<ProxyConfig>,
+    pub connection: ConnectionOptions,
+    pub templates: bool,
+}
+
+/// With HTTP/2 the requests of the workers sharing a client are streams of
+/// the same connection.
+pub struct SharedClients<T> {
+    slots: Vec<Arc<SharedClient<T>>>,
+}
+
+/// Created by the first worker that uses it.
+pub struct SharedClient<T> {
+    pub client: OnceCell<T>,
+    streams: Option<Semaphore>,
+}
+
+impl<T> SharedClient<T> {
+    /// The stream is released when the permit is dropped.
+    pub async fn stream(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.streams {
+            Some(streams) => streams.acquire().await.ok(),
+            None => None,
+        }
+    }
+}
+
+impl<T> SharedClients<T> {
+    /// Without `count` every worker has its own client.
+    pub fn new(count: Option<u64>, max_streams: Option<u32>) -> Self {
+        SharedClients {
+            slots: (0..count.unwrap_or(0))
+                .map(|_| {
+                    Arc::new(SharedClient {
+                        client: OnceCell::new(),
+                        streams: max_streams.map(|streams| Semaphore::new(streams as usize)),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn slot(&self, worker_id: u64) -> Option<Arc<SharedClient<T>>> {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        let index = (worker_id % self.slots.len() as u64) as usize;
+        Some(Arc::clone(&self.slots[index]))
+    }
+}
+
+impl<T> Clone for SharedClients<T> {
+    fn clone(&self) -> Self {
+        SharedClients {
+            slots: self.slots.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestClients {
+    pub http: SharedClients<HttpClient>,
+    pub grpc: SharedClients<Channel>,
+    /// Loaded once for all workers
+    pub grpc_descriptors: Arc<OnceCell<DescriptorPool>>,
+}
+
+impl RequestClients {
+    /// For requests sent before the test, so that the shared clients are
+    /// created by the workers and record into their metrics.
+    pub fn unshared(&self) -> Self {
+        RequestClients {
+            http: SharedClients::new(None, None),
+            grpc: SharedClients::new(None, None),
+            grpc_descriptors: Arc::clone(&self.grpc_descriptors),
+        }
+    }
+
+    pub fn new(connection: &ConnectionOptions) -> Self {
+        let (clients, max_streams) = (connection.clients, connection.max_concurrent_streams);
+        RequestClients {
+            http: SharedClients::new(clients, max_streams),
+            grpc: SharedClients::new(clients, max_streams),
+            grpc_descriptors: Arc::default(),
+        }
+    }
+}
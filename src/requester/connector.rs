@@ -0,0 +1,279 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use http::Uri;
+use hyper_util::{
+    client::legacy::connect::{Connected, Connection},
+    rt::TokioIo,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+use tokio_native_tls::{TlsConnector, TlsStream};
+
+use crate::{
+    metrics::{metrics::Metrics, timing::Timing},
+    requester::{proxy::ProxyConfig, tls::TlsConfig},
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Opens TCP and TLS connections while recording how long DNS resolution,
+/// the TCP connect and the TLS handshake took.
+#[derive(Clone)]
+pub struct TimingConnector {
+    metrics: Arc<Metrics>,
+    tls: TlsConnector,
+    sni: Option<String>,
+    proxy: Arc<ProxyConfig>,
+    /// Whether plain HTTP requests are forwarded by the proxy instead of
+    /// being sent through a tunnel
+    forward: bool,
+    limit: Option<Arc<ConnectionLimit>>,
+}
+
+/// Open connections of a client by host. A connection holds a permit until
+/// it is closed, so that requests wait for a connection to become free.
+struct ConnectionLimit {
+    max: usize,
+    hosts: Mutex<HashMap<(String, u16), Arc<Semaphore>>>,
+}
+
+/// When the connection a response was received over was established, after
+/// the TLS handshake. Set as an extension of every response of the client.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectedAt(pub Instant);
+
+impl TimingConnector {
+    /// `alpn` lists the protocols offered in TLS handshakes, such as `h2`.
+    pub fn new(
+        metrics: Arc<Metrics>,
+        tls: &TlsConfig,
+        alpn: &[&str],
+    ) -> Result<Self, native_tls::Error> {
+        Ok(Self {
+            metrics,
+            tls: TlsConnector::from(tls.connector(alpn)?),
+            sni: tls.sni().map(str::to_string),
+            proxy: Arc::default(),
+            forward: false,
+            limit: None,
+        })
+    }
+
+    /// Opens at most `max` connections to every host.
+    pub fn with_connection_limit(mut self, max: Option<usize>) -> Self {
+        self.limit = max.map(|max| {
+            Arc::new(ConnectionLimit {
+                max,
+                hosts: Mutex::default(),
+            })
+        });
+        self
+    }
+
+    /// Connects through the proxies of `proxy`. With `forward` plain HTTP
+    /// requests are sent to the proxy to forward them, which only works for
+    /// HTTP/1.1, otherwise they go through a tunnel like TLS connections.
+    pub fn with_proxy(mut self, proxy: Arc<ProxyConfig>, forward: bool) -> Self {
+        self.proxy = proxy;
+        self.forward = forward;
+        self
+    }
+
+    /// Opens a connection to the host of `uri`, with TLS for `https` and `wss`.
+    pub async fn connect_stream(&self, uri: &Uri) -> Result<MaybeTlsStream, BoxError> {
+        let host = uri
+            .host()
+            .ok_or_else(|| format!("missing host in '{}'", uri))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let is_tls = matches!(uri.scheme_str(), Some("https") | Some("wss"));
+        let port = uri.port_u16().unwrap_or(if is_tls { 443 } else { 80 });
+
+        let permit = match &self.limit {
+            Some(limit) => {
+                let semaphore = Arc::clone(
+                    limit
+                        .hosts
+                        .lock()
+                        .unwrap()
+                        .entry((host.clone(), port))
+                        .or_insert_with(|| Arc::new(Semaphore::new(limit.max))),
+                );
+                Some(semaphore.acquire_owned().await?)
+            }
+            None => None,
+        };
+
+        let proxy = self.proxy.for_uri(uri);
+        let (connect_host, connect_port) = match proxy {
+            Some(proxy) => (proxy.host.as_str(), proxy.port),
+            None => (host.as_str(), port),
+        };
+
+        let start = Instant::now();
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((connect_host, connect_port))
+            .await?
+            .collect();
+        self.record(Timing::Dns, start).await;
+
+        // Opening the tunnel through a proxy counts as part of the connect
+        let start = Instant::now();
+        let mut tcp = connect_any(&addrs).await?;
+        tcp.set_nodelay(true)?;
+        let forwarded = match proxy {
+            Some(_) if !is_tls && self.forward => true,
+            Some(proxy) => {
+                proxy.tunnel(&mut tcp, &host, port).await?;
+                false
+            }
+            None => false,
+        };
+        self.record(Timing::Connect, start).await;
+        self.metrics.increment_connections_opened().await;
+
+        if !is_tls {
+            return Ok(MaybeTlsStream::new(Stream::Plain(tcp), forwarded, permit));
+        }
+
+        let start = Instant::now();
+        let server_name = self.sni.as_deref().unwrap_or(&host);
+        let tls = self.tls.connect(server_name, tcp).await?;
+        self.record(Timing::Tls, start).await;
+
+        Ok(MaybeTlsStream::new(
+            Stream::Tls(Box::new(tls)),
+            false,
+            permit,
+        ))
+    }
+
+    async fn record(&self, timing: Timing, start: Instant) {
+        self.metrics
+            .record_timing(timing, Metrics::micros(start.elapsed()))
+            .await;
+    }
+}
+
+async fn connect_any(addrs: &[SocketAddr]) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "host resolved to no addresses");
+
+    for addr in addrs {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+impl tower::Service<Uri> for TimingConnector {
+    type Response = TokioIo<MaybeTlsStream>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = self.clone();
+        Box::pin(async move { Ok(TokioIo::new(connector.connect_stream(&uri).await?)) })
+    }
+}
+
+/// An established connection, with or without TLS.
+pub struct MaybeTlsStream {
+    stream: Stream,
+    connected_at: Instant,
+    /// Requests are sent to a proxy that forwards them
+    forwarded: bool,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl MaybeTlsStream {
+    fn new(stream: Stream, forwarded: bool, permit: Option<OwnedSemaphorePermit>) -> Self {
+        MaybeTlsStream {
+            stream,
+            connected_at: Instant::now(),
+            forwarded,
+            _permit: permit,
+        }
+    }
+}
+
+impl Connection for MaybeTlsStream {
+    fn connected(&self) -> Connected {
+        let connected = Connected::new()
+            .proxy(self.forwarded)
+            .extra(ConnectedAt(self.connected_at));
+
+        let Stream::Tls(stream) = &self.stream else {
+            return connected;
+        };
+
+        // Lets the client speak HTTP/2 on connections where the server chose it
+        match stream.get_ref().negotiated_alpn() {
+            Ok(Some(protocol)) if protocol == b"h2" => connected.negotiated_h2(),
+            _ => connected,
+        }
+    }
+}
+
+impl AsyncRead for MaybeTlsStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for MaybeTlsStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Stream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Stream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().stream {
+            Stream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Stream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
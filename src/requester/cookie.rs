@@ -0,0 +1,389 @@
+use std::time::{Duration, SystemTime};
+
+use http::{
+    header::{HeaderValue, SET_COOKIE},
+    HeaderMap, Uri,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CookieMode {
+    /// Cookies set by responses are ignored, only seeded cookies are sent
+    #[default]
+    Off,
+    /// Every worker keeps its cookies for the whole run, like a returning user
+    PerWorker,
+    /// Cookies are cleared at the start of every iteration, like a new visitor
+    PerIteration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    mode: CookieMode,
+    /// What the jar goes back to when it is cleared
+    seed: Vec<Cookie>,
+    cookies: Vec<Cookie>,
+}
+
+#[derive(Debug, Clone)]
+struct Cookie {
+    name: String,
+    value: String,
+    /// `None` for seeded cookies, sent to any host
+    domain: Option<String>,
+    include_subdomains: bool,
+    path: String,
+    secure: bool,
+    expires: Option<SystemTime>,
+}
+
+impl CookieJar {
+    pub fn new(mode: CookieMode, seed: Vec<(String, String)>) -> Self {
+        let seed: Vec<Cookie> = seed
+            .into_iter()
+            .map(|(name, value)| Cookie {
+                name,
+                value,
+                domain: None,
+                include_subdomains: false,
+                path: "/".to_string(),
+                secure: false,
+                expires: None,
+            })
+            .collect();
+
+        CookieJar {
+            mode,
+            cookies: seed.clone(),
+            seed,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.mode != CookieMode::Off
+    }
+
+    pub fn start_iteration(&mut self) {
+        if self.mode == CookieMode::PerIteration {
+            self.cookies.clone_from(&self.seed);
+        }
+    }
+
+    pub fn header(&self, uri: &Uri) -> Option<HeaderValue> {
+        let host = uri.host().unwrap_or_default();
+        let path = uri.path();
+        let secure = matches!(uri.scheme_str(), Some("https") | Some("wss"));
+        let now = SystemTime::now();
+
+        let pairs: Vec<String> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(host, path, secure) && !cookie.is_expired(now))
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        if pairs.is_empty() {
+            return None;
+        }
+
+        HeaderValue::from_str(&pairs.join("; ")).ok()
+    }
+
+    /// Cookies that already expired or have an empty value are removed.
+    pub fn store(&mut self, uri: &Uri, headers: &HeaderMap) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        for header in headers.get_all(SET_COOKIE) {
+            let Ok(header) = header.to_str() else {
+                continue;
+            };
+            let now = SystemTime::now();
+            let Some(cookie) = Cookie::parse(header, &host, uri.path(), now) else {
+                continue;
+            };
+
+            // A cookie set by a response also replaces the seeded one
+            self.cookies.retain(|stored| {
+                stored.name != cookie.name
+                    || (stored.domain.is_some()
+                        && (stored.domain != cookie.domain || stored.path != cookie.path))
+            });
+            if !cookie.is_expired(now) && !cookie.value.is_empty() {
+                self.cookies.push(cookie);
+            }
+        }
+    }
+}
+
+impl Cookie {
+    /// Attributes other than `Domain`, `Path`, `Secure`, `Max-Age` and
+    /// `Expires` are ignored.
+    fn parse(header: &str, host: &str, request_path: &str, now: SystemTime) -> Option<Self> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: Some(host.to_string()),
+            include_subdomains: false,
+            path: default_path(request_path),
+            secure: false,
+            expires: None,
+        };
+        // Max-Age takes precedence over Expires
+        let mut max_age = None;
+
+        for attribute in parts {
+            let attribute = attribute.trim();
+            if attribute.eq_ignore_ascii_case("secure") {
+                cookie.secure = true;
+                continue;
+            }
+            let Some((key, value)) = attribute.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            if key.trim().eq_ignore_ascii_case("max-age") {
+                if let Ok(age) = value.parse::<i64>() {
+                    let age = Duration::from_secs(age.max(0) as u64);
+                    max_age = Some(if age.is_zero() {
+                        SystemTime::UNIX_EPOCH
+                    } else {
+                        now + age
+                    });
+                }
+            } else if key.trim().eq_ignore_ascii_case("expires") {
+                // Also accepts the dashes of the old Netscape format, after
+                // the RFC 850 format that has dashes too
+                let expires = httpdate::parse_http_date(value)
+                    .or_else(|_| httpdate::parse_http_date(&value.replace('-', " ")));
+                if let Ok(expires) = expires {
+                    cookie.expires = Some(expires);
+                }
+            } else if key.trim().eq_ignore_ascii_case("domain") {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                // A host can only set cookies for itself and its parents
+                if !domain.is_empty() && domain_matches(host, &domain) {
+                    cookie.domain = Some(domain);
+                    cookie.include_subdomains = true;
+                }
+            } else if key.trim().eq_ignore_ascii_case("path") && value.starts_with('/') {
+                cookie.path = value.to_string();
+            }
+        }
+
+        if max_age.is_some() {
+            cookie.expires = max_age;
+        }
+
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, host: &str, path: &str, secure: bool) -> bool {
+        if self.secure && !secure {
+            return false;
+        }
+
+        let host_matches = match &self.domain {
+            None => true,
+            Some(domain) if self.include_subdomains => domain_matches(host, domain),
+            Some(domain) => host.eq_ignore_ascii_case(domain),
+        };
+
+        host_matches && path_matches(path, &self.path)
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Path of cookies set without one.
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => request_path[..index].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn domain_matching() {
+        let cases = [
+            ("example.com", "example.com", true),
+            ("EXAMPLE.com", "example.com", true),
+            ("api.example.com", "example.com", true),
+            ("a.b.example.com", "example.com", true),
+            ("badexample.com", "example.com", false),
+            ("example.com", "api.example.com", false),
+            ("example.org", "example.com", false),
+        ];
+
+        for (host, domain, expected) in cases {
+            assert_eq!(
+                domain_matches(host, domain),
+                expected,
+                "{} {}",
+                host,
+                domain
+            );
+        }
+    }
+
+    #[test]
+    fn path_matching() {
+        let cases = [
+            ("/", "/", true),
+            ("/files", "/", true),
+            ("/files", "/files", true),
+            ("/files/1", "/files", true),
+            ("/files/1", "/files/", true),
+            ("/filesystem", "/files", false),
+            ("/", "/files", false),
+            ("/other/files", "/files", false),
+        ];
+
+        for (path, cookie_path, expected) in cases {
+            assert_eq!(
+                path_matches(path, cookie_path),
+                expected,
+                "{} {}",
+                path,
+                cookie_path
+            );
+        }
+    }
+
+    #[test]
+    fn default_paths() {
+        let cases = [
+            ("", "/"),
+            ("/", "/"),
+            ("/login", "/"),
+            ("/api/login", "/api"),
+            ("/api/v1/", "/api/v1"),
+        ];
+
+        for (request_path, expected) in cases {
+            assert_eq!(default_path(request_path), expected, "{}", request_path);
+        }
+    }
+
+    #[test]
+    fn cookies_match_requests() {
+        let now = SystemTime::now();
+        // Set-Cookie header of a response from api.example.com/auth/login,
+        // then host, path, TLS of a request and whether it gets the cookie
+        let cases = [
+            ("id=1", "api.example.com", "/auth", false, true),
+            ("id=1", "api.example.com", "/auth/me", false, true),
+            ("id=1", "api.example.com", "/", false, false),
+            ("id=1", "www.example.com", "/auth", false, false),
+            ("id=1; Path=/", "api.example.com", "/", false, true),
+            (
+                "id=1; Domain=example.com; Path=/",
+                "www.example.com",
+                "/",
+                false,
+                true,
+            ),
+            (
+                "id=1; Domain=.example.com; Path=/",
+                "example.com",
+                "/",
+                false,
+                true,
+            ),
+            (
+                "id=1; Domain=other.com; Path=/",
+                "other.com",
+                "/",
+                false,
+                false,
+            ),
+            (
+                "id=1; Domain=other.com; Path=/",
+                "api.example.com",
+                "/",
+                false,
+                true,
+            ),
+            ("id=1; Path=/; Secure", "api.example.com", "/", false, false),
+            ("id=1; Path=/; Secure", "api.example.com", "/", true, true),
+        ];
+
+        for (header, host, path, secure, expected) in cases {
+            let cookie = Cookie::parse(header, "api.example.com", "/auth/login", now).unwrap();
+            assert_eq!(
+                cookie.matches(host, path, secure),
+                expected,
+                "{} for {}{}",
+                header,
+                host,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn cookie_expiry() {
+        let now = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        let cases = [
+            ("id=1", false),
+            ("id=1; Max-Age=60", false),
+            ("id=1; Max-Age=0", true),
+            ("id=1; Max-Age=-1", true),
+            ("id=1; Expires=Sun, 06 Nov 1994 08:49:36 GMT", true),
+            ("id=1; Expires=Mon, 07 Nov 1994 08:49:37 GMT", false),
+            ("id=1; Expires=Sun, 06-Nov-1994 08:49:36 GMT", true),
+            ("id=1; Expires=Sunday, 06-Nov-94 08:49:36 GMT", true),
+            ("id=1; Expires=Monday, 07-Nov-94 08:49:37 GMT", false),
+            (
+                "id=1; Max-Age=60; Expires=Sun, 06 Nov 1994 08:49:36 GMT",
+                false,
+            ),
+            ("id=1; Expires=never", false),
+        ];
+
+        for (header, expired) in cases {
+            let cookie = Cookie::parse(header, "example.com", "/", now).unwrap();
+            assert_eq!(cookie.is_expired(now), expired, "{}", header);
+        }
+    }
+
+    #[test]
+    fn rejects_headers_without_a_name() {
+        let now = SystemTime::now();
+        for header in ["", "id", "=1", " =1; Path=/"] {
+            assert!(
+                Cookie::parse(header, "example.com", "/", now).is_none(),
+                "{}",
+                header
+            );
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor, MethodDescriptor,
+    ServiceDescriptor,
+};
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+use protox::{
+    file::{
+        ChainFileResolver, DescriptorSetFileResolver, FileResolver, GoogleFileResolver,
+        IncludeFileResolver,
+    },
+    Compiler,
+};
+use tonic::transport::Channel;
+
+use crate::requester::reflection::ReflectionClient;
+
+#[derive(Debug, Clone, Default)]
+pub struct ProtoSources {
+    /// Descriptor sets such as `buf build -o image.binpb` are accepted too
+    pub files: Vec<String>,
+    pub import_paths: Vec<String>,
+}
+
+impl ProtoSources {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// Without proto files the descriptors of `symbols` come from server
+/// reflection.
+pub async fn load(
+    sources: &ProtoSources,
+    channel: &Channel,
+    symbols: &[String],
+) -> Result<DescriptorPool> {
+    if sources.is_empty() {
+        return ReflectionClient::new(channel.clone())
+            .descriptors(symbols)
+            .await;
+    }
+
+    load_files(sources)
+}
+
+fn load_files(sources: &ProtoSources) -> Result<DescriptorPool> {
+    let (protos, sets): (Vec<&String>, Vec<&String>) = sources
+        .files
+        .iter()
+        .partition(|path| path.ends_with(".proto"));
+
+    let mut pool = DescriptorPool::new();
+    let mut loaded_sets = Vec::new();
+
+    // Descriptor sets must contain their imports, unlike proto files they
+    // cannot be looked up in the import paths
+    for path in sets {
+        let bytes = std::fs::read(path).with_context(|| format!("failed to read '{}'", path))?;
+        let set = FileDescriptorSet::decode(&bytes[..])
+            .with_context(|| format!("'{}' is not a descriptor set", path))?;
+
+        let missing = missing_imports(&set.file, &pool);
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "descriptor set '{}' lacks imported files: {}, build it with its imports, for example without --exclude-imports for buf or with --include_imports for protoc",
+                path,
+                missing.join(", ")
+            ));
+        }
+        pool.add_file_descriptor_protos(set.file.clone())
+            .with_context(|| format!("invalid descriptor set '{}'", path))?;
+        loaded_sets.push(set);
+    }
+
+    if protos.is_empty() {
+        return Ok(pool);
+    }
+
+    // Proto files are looked up relative to the import paths, then to their
+    // own directory
+    let mut includes: Vec<PathBuf> = sources.import_paths.iter().map(PathBuf::from).collect();
+    for path in &protos {
+        if !Path::new(path.as_str()).is_file() {
+            return Err(anyhow!("proto file '{}' not found", path));
+        }
+        let parent = Path::new(path.as_str())
+            .parent()
+            .map(|parent| parent.to_path_buf())
+            .unwrap_or_default();
+        if !includes.contains(&parent) {
+            includes.push(parent);
+        }
+    }
+
+    let unresolved = unresolved_imports(&protos, &resolver(&includes, &loaded_sets));
+    if !unresolved.is_empty() {
+        let mut message = String::from("unresolved imports:");
+        for (import, importers) in &unresolved {
+            let importers: Vec<&str> = importers.iter().map(String::as_str).collect();
+            let _ = write!(
+                message,
+                "\n  {} (imported by {})",
+                import,
+                importers.join(", ")
+            );
+        }
+        let _ = write!(
+            message,
+            "\nadd the directories that contain them with --import-path"
+        );
+        return Err(anyhow!(message));
+    }
+
+    let mut compiler = Compiler::with_file_resolver(resolver(&includes, &loaded_sets));
+    compiler.include_imports(true);
+    compiler.open_files(&protos).map_err(|e| match e.file() {
+        Some(file) => anyhow!("{}: {}", file, e),
+        None => anyhow!(e),
+    })?;
+    pool.add_file_descriptor_protos(compiler.file_descriptor_set().file)?;
+
+    Ok(pool)
+}
+
+/// Descriptor sets come after the include paths so that proto files can
+/// import their files.
+fn resolver(includes: &[PathBuf], sets: &[FileDescriptorSet]) -> ChainFileResolver {
+    let mut resolver = ChainFileResolver::new();
+    for include in includes {
+        resolver.add(IncludeFileResolver::new(include.clone()));
+    }
+    for set in sets {
+        resolver.add(DescriptorSetFileResolver::new(set.clone()));
+    }
+    resolver.add(GoogleFileResolver::new());
+
+    resolver
+}
+
+fn missing_imports(files: &[FileDescriptorProto], pool: &DescriptorPool) -> Vec<String> {
+    let names: BTreeSet<&str> = files.iter().map(|file| file.name()).collect();
+
+    files
+        .iter()
+        .flat_map(|file| &file.dependency)
+        .filter(|import| {
+            !names.contains(import.as_str()) && pool.get_file_by_name(import).is_none()
+        })
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Compiling stops at the first missing import, this lists all of them.
+fn unresolved_imports(
+    protos: &[&String],
+    resolver: &ChainFileResolver,
+) -> BTreeMap<String, BTreeSet<String>> {
+    let mut unresolved: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut visited = BTreeSet::new();
+    let mut queue: VecDeque<String> = protos
+        .iter()
+        .filter_map(|path| resolver.resolve_path(Path::new(path.as_str())))
+        .collect();
+
+    while let Some(name) = queue.pop_front() {
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        // Other errors, such as syntax errors, are reported by the compiler
+        let Ok(file) = resolver.open_file(&name) else {
+            continue;
+        };
+
+        for import in &file.file_descriptor_proto().dependency {
+            match resolver.open_file(import) {
+                Err(e) if e.is_file_not_found() => {
+                    unresolved
+                        .entry(import.clone())
+                        .or_default()
+                        .insert(name.clone());
+                }
+                _ => queue.push_back(import.clone()),
+            }
+        }
+    }
+
+    unresolved
+}
+
+pub fn split_method(full_method: &str) -> Result<(&str, &str)> {
+    full_method
+        .rsplit_once('/')
+        .or_else(|| full_method.rsplit_once('.'))
+        .map(|(service, method)| (service.trim(), method.trim()))
+        .ok_or_else(|| {
+            anyhow!(
+                "invalid format: expected Service.Method or Service/Method, got: '{}'",
+                full_method
+            )
+        })
+}
+
+pub fn get_method(pool: &DescriptorPool, full_method: &str) -> Result<MethodDescriptor> {
+    let (service_name, method_name) = split_method(full_method)?;
+
+    let service = pool
+        .get_service_by_name(service_name)
+        .ok_or_else(|| anyhow!("service not found: {}", service_name))?;
+
+    let methods: Vec<_> = service.methods().collect();
+    let method = methods
+        .into_iter()
+        .find(|m| m.name() == method_name)
+        .ok_or_else(|| anyhow!("method not found: {}", method_name))?;
+
+    Ok(method)
+}
+
+fn reflection_channel(channel: Option<&Channel>) -> Result<&Channel> {
+    channel.ok_or_else(|| anyhow!("pass the proto file with --proto or a server to reflect"))
+}
+
+/// `channel` is only connected when there are no proto files.
+pub async fn list(sources: &ProtoSources, channel: Option<&Channel>) -> Result<String> {
+    let (pool, services) = if sources.is_empty() {
+        let mut client = ReflectionClient::new(reflection_channel(channel)?.clone());
+        let services = client.list_services().await?;
+        (client.descriptors(&services).await?, services)
+    } else {
+        let pool = load_files(sources)?;
+        let services = pool
+            .services()
+            .map(|service| service.full_name().to_string())
+            .collect();
+        (pool, services)
+    };
+
+    let mut output = String::new();
+    for name in services {
+        let _ = writeln!(output, "{}", name);
+        if let Some(service) = pool.get_service_by_name(&name) {
+            for method in service.methods() {
+                let _ = writeln!(output, "   {}", signature(&method));
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// `channel` is only connected when there are no proto files.
+pub async fn describe(
+    sources: &ProtoSources,
+    channel: Option<&Channel>,
+    symbol: &str,
+) -> Result<String> {
+    let symbol = symbol.replace('/', ".");
+    let pool = if sources.is_empty() {
+        ReflectionClient::new(reflection_channel(channel)?.clone())
+            .descriptors(std::slice::from_ref(&symbol))
+            .await?
+    } else {
+        load_files(sources)?
+    };
+
+    let mut output = String::new();
+    let mut messages = Vec::new();
+
+    if let Some(service) = pool.get_service_by_name(&symbol) {
+        format_service(&service, &mut output);
+        for method in service.methods() {
+            messages.extend([method.input(), method.output()]);
+        }
+    } else if let Some(message) = pool.get_message_by_name(&symbol) {
+        messages.push(message);
+    } else if let Some(enumeration) = pool.get_enum_by_name(&symbol) {
+        format_enum(&enumeration, &mut output);
+    } else {
+        let method = get_method(&pool, &symbol)
+            .map_err(|_| anyhow!("no service, method, message or enum named '{}'", symbol))?;
+        let _ = writeln!(output, "{}", signature(&method));
+        messages.extend([method.input(), method.output()]);
+    }
+
+    // Every message and enum the fields refer to, each printed once
+    let mut queue: VecDeque<MessageDescriptor> = messages.into();
+    let mut printed = BTreeSet::new();
+    while let Some(message) = queue.pop_front() {
+        if !printed.insert(message.full_name().to_string()) {
+            continue;
+        }
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        format_message(&message, &mut output);
+
+        for field in message.fields() {
+            let kind = match field.kind() {
+                Kind::Message(entry) if entry.is_map_entry() => {
+                    entry.map_entry_value_field().kind()
+                }
+                kind => kind,
+            };
+            match kind {
+                Kind::Message(message) => queue.push_back(message),
+                Kind::Enum(enumeration) if printed.insert(enumeration.full_name().to_string()) => {
+                    output.push('\n');
+                    format_enum(&enumeration, &mut output);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+fn signature(method: &MethodDescriptor) -> String {
+    let stream = |streaming: bool| if streaming { "stream " } else { "" };
+    format!(
+        "rpc {}({}{}) returns ({}{});",
+        method.name(),
+        stream(method.is_client_streaming()),
+        method.input().full_name(),
+        stream(method.is_server_streaming()),
+        method.output().full_name()
+    )
+}
+
+fn format_service(service: &ServiceDescriptor, output: &mut String) {
+    let _ = writeln!(output, "service {} {{", service.full_name());
+    for method in service.methods() {
+        let _ = writeln!(output, "  {}", signature(&method));
+    }
+    let _ = writeln!(output, "}}");
+}
+
+fn format_message(message: &MessageDescriptor, output: &mut String) {
+    let _ = writeln!(output, "message {} {{", message.full_name());
+
+    // Fields of a oneof are printed in its block
+    for field in message.fields() {
+        if field
+            .containing_oneof()
+            .is_none_or(|oneof| oneof.is_synthetic())
+        {
+            let _ = writeln!(output, "  {}", format_field(&field));
+        }
+    }
+    for oneof in message.oneofs().filter(|oneof| !oneof.is_synthetic()) {
+        let _ = writeln!(output, "  oneof {} {{", oneof.name());
+        for field in oneof.fields() {
+            let _ = writeln!(output, "    {}", format_field(&field));
+        }
+        let _ = writeln!(output, "  }}");
+    }
+
+    let _ = writeln!(output, "}}");
+}
+
+fn format_enum(enumeration: &EnumDescriptor, output: &mut String) {
+    let _ = writeln!(output, "enum {} {{", enumeration.full_name());
+    for value in enumeration.values() {
+        let _ = writeln!(output, "  {} = {};", value.name(), value.number());
+    }
+    let _ = writeln!(output, "}}");
+}
+
+fn format_field(field: &FieldDescriptor) -> String {
+    let kind = match field.kind() {
+        Kind::Message(entry) if field.is_map() => format!(
+            "map<{}, {}>",
+            kind_name(&entry.map_entry_key_field().kind()),
+            kind_name(&entry.map_entry_value_field().kind())
+        ),
+        kind if field.is_list() => format!("repeated {}", kind_name(&kind)),
+        kind if field.field_descriptor_proto().proto3_optional() => {
+            format!("optional {}", kind_name(&kind))
+        }
+        kind => kind_name(&kind),
+    };
+
+    format!("{} {} = {};", kind, field.name(), field.number())
+}
+
+fn kind_name(kind: &Kind) -> String {
+    match kind {
+        Kind::Double => "double".to_string(),
+        Kind::Float => "float".to_string(),
+        Kind::Int32 => "int32".to_string(),
+        Kind::Int64 => "int64".to_string(),
+        Kind::Uint32 => "uint32".to_string(),
+        Kind::Uint64 => "uint64".to_string(),
+        Kind::Sint32 => "sint32".to_string(),
+        Kind::Sint64 => "sint64".to_string(),
+        Kind::Fixed32 => "fixed32".to_string(),
+        Kind::Fixed64 => "fixed64".to_string(),
+        Kind::Sfixed32 => "sfixed32".to_string(),
+        Kind::Sfixed64 => "sfixed64".to_string(),
+        Kind::Bool => "bool".to_string(),
+        Kind::String => "string".to_string(),
+        Kind::Bytes => "bytes".to_string(),
+        Kind::Message(message) => message.full_name().to_string(),
+        Kind::Enum(enumeration) => enumeration.full_name().to_string(),
+    }
+}
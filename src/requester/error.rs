@@ -1,12 +1,59 @@
+use std::fmt;
+
 #[derive(Debug, Clone)]
 pub enum RequestError {
-    Network,
+    Network(String),
     Timeout,
     ConfigError(String),
     InvalidRequest(String),
     RequestFailed(String),
     ConnectionError(String),
     InternalError(String),
-    ServerError(String),
-    GrpcError(String),
+    HttpStatus(u16),
+    GrpcStatus(tonic::Code, String),
+    /// A check on the response failed, holds the name of the first one
+    CheckFailed(String),
+    /// A value to extract into a variable is missing from the response
+    ExtractFailed(String),
+}
+
+impl RequestError {
+    /// Short name of the error variant used to group failures in reports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RequestError::Network(_) => "network",
+            RequestError::Timeout => "timeout",
+            RequestError::ConfigError(_) => "config_error",
+            RequestError::InvalidRequest(_) => "invalid_request",
+            RequestError::RequestFailed(_) => "request_failed",
+            RequestError::ConnectionError(_) => "connection_error",
+            RequestError::InternalError(_) => "internal_error",
+            RequestError::HttpStatus(_) => "http_status",
+            RequestError::GrpcStatus(_, _) => "grpc_status",
+            RequestError::CheckFailed(_) => "check_failed",
+            RequestError::ExtractFailed(_) => "extract_failed",
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Network(message) => write!(f, "network error: {}", message),
+            RequestError::Timeout => write!(f, "request timed out"),
+            RequestError::HttpStatus(status) => {
+                write!(f, "service returned {} status code", status)
+            }
+            RequestError::GrpcStatus(code, message) => write!(f, "{:?}: {}", code, message),
+            RequestError::CheckFailed(check) => write!(f, "check '{}' failed", check),
+            RequestError::ExtractFailed(extractor) => {
+                write!(f, "response has no value for '{}'", extractor)
+            }
+            RequestError::ConfigError(message)
+            | RequestError::InvalidRequest(message)
+            | RequestError::RequestFailed(message)
+            | RequestError::ConnectionError(message)
+            | RequestError::InternalError(message) => write!(f, "{}", message),
+        }
+    }
 }
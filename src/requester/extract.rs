@@ -0,0 +1,95 @@
+use std::str::FromStr;
+
+use http::header::{HeaderName, SET_COOKIE};
+use regex::Regex;
+
+use crate::requester::check::{JsonPath, Response};
+
+/// Source of a value taken from a response into a variable of the worker,
+/// such as `json:$.token`, `regex:id=(\d+)`, `header:Location` or
+/// `cookie:session`.
+#[derive(Debug, Clone)]
+pub struct Extractor {
+    expression: String,
+    kind: ExtractorKind,
+}
+
+#[derive(Debug, Clone)]
+enum ExtractorKind {
+    /// Value at a JSONPath of the body, strings without their quotes
+    Json(JsonPath),
+    /// First capture group of the regex in the body, or the whole match
+    Regex(Regex),
+    Header(HeaderName),
+    /// Value of a cookie set by the response
+    Cookie(String),
+}
+
+impl Extractor {
+    pub fn name(&self) -> &str {
+        &self.expression
+    }
+
+    /// Returns the value, or `None` when the response does not have it.
+    pub fn extract(&self, response: &Response) -> Option<String> {
+        match &self.kind {
+            ExtractorKind::Json(path) => {
+                let body: serde_json::Value = serde_json::from_slice(response.body).ok()?;
+                match path.lookup(&body)? {
+                    serde_json::Value::String(text) => Some(text.clone()),
+                    value => Some(value.to_string()),
+                }
+            }
+            ExtractorKind::Regex(regex) => {
+                let body = String::from_utf8_lossy(response.body);
+                let captures = regex.captures(&body)?;
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|value| value.as_str().to_string())
+            }
+            ExtractorKind::Header(name) => response
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            ExtractorKind::Cookie(name) => response
+                .headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .filter_map(|cookie| cookie.split(';').next()?.split_once('='))
+                .find(|(cookie, _)| cookie.trim() == name)
+                .map(|(_, value)| value.trim().to_string()),
+        }
+    }
+}
+
+impl FromStr for Extractor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, argument) = s
+            .split_once(':')
+            .ok_or_else(|| format!("invalid extractor '{}': expected KIND:ARGUMENT", s))?;
+
+        let kind = match kind.trim() {
+            "json" => ExtractorKind::Json(argument.trim().parse()?),
+            "regex" => ExtractorKind::Regex(
+                Regex::new(argument)
+                    .map_err(|e| format!("invalid regex in extractor '{}': {}", s, e))?,
+            ),
+            "header" => ExtractorKind::Header(
+                HeaderName::from_bytes(argument.trim().as_bytes())
+                    .map_err(|_| format!("invalid header name in extractor '{}'", s))?,
+            ),
+            "cookie" => ExtractorKind::Cookie(argument.trim().to_string()),
+            other => return Err(format!("unknown extractor '{}'", other)),
+        };
+
+        Ok(Extractor {
+            expression: s.to_string(),
+            kind,
+        })
+    }
+}
@@ -1,19 +1,35 @@
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
+use base64::Engine;
 use bytes::BufMut;
 use prost::Message;
 use prost_reflect::MethodDescriptor;
-use prost_reflect::{DescriptorPool, DynamicMessage};
+use prost_reflect::{DescriptorPool, DynamicMessage, SerializeOptions};
 use serde_json::Value as JsonValue;
 use std::sync::OnceLock;
 
-use crate::metrics::metrics::Metrics;
+use crate::metrics::{metrics::Metrics, timing::Timing};
+use crate::requester::check::{Check, GrpcResponse};
+use crate::requester::client::SharedClient;
+use crate::requester::connector::TimingConnector;
+use crate::requester::descriptors::{self, ProtoSources};
 use crate::requester::error::RequestError;
+use crate::requester::tls::TlsConfig;
+use crate::requester::wire::{WireBytes, WireCounter};
 use crate::requester::Requester;
+use crate::template::{context::RequestContext, template::Template};
+use http::{uri::PathAndQuery, HeaderMap};
+use serde::Deserialize;
+use tokio::sync::OnceCell;
 use tonic::client::Grpc;
+use tonic::codec::CompressionEncoding;
+use tonic::metadata::{
+    AsciiMetadataKey, AsciiMetadataValue, BinaryMetadataKey, BinaryMetadataValue, MetadataMap,
+};
 use tonic::transport::Channel;
+use tonic::Streaming;
 
 use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
 use tonic::Status;
@@ -21,23 +37,95 @@ use tonic::Status;
 pub struct GrpcRequester<'a> {
     metrics: &'a Arc<Metrics>,
     address: String,
-    proto: String,
+    /// Proto files and descriptor sets, none to use server reflection
+    protos: ProtoSources,
     method: String,
     data: Option<String>,
     timeout: u64,
+    /// Messages sent per call by client streaming and bidirectional methods
+    messages: u64,
+    /// Metadata sent with every call
+    metadata: MetadataMap,
+    /// Deadline of every call, sent to the server as `grpc-timeout`
+    deadline: Option<Duration>,
+    compression: Option<GrpcCompression>,
+    templates: bool,
+    checks: Vec<Check>,
+    tls: Arc<TlsConfig>,
+    /// Descriptors shared with other workers, loaded by the first one
+    descriptors: Arc<OnceCell<DescriptorPool>>,
+    /// Channel shared with other workers, connected by the first one to use it
+    shared_channel: Option<Arc<SharedClient<Channel>>>,
 
     path_uri: OnceLock<http::Uri>,
+    call_kind: OnceLock<CallKind>,
     codec: OnceLock<DynamicCodec>,
-    req_msg: OnceLock<DynamicMessage>,
-    client: OnceLock<Grpc<Channel>>,
+    req_msg: OnceLock<MessageTemplate>,
     channel: OnceLock<Channel>,
 }
 
+/// Compression of the request messages, also the only encoding accepted for
+/// the response messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrpcCompression {
+    Gzip,
+    Zstd,
+}
+
+impl GrpcCompression {
+    fn encoding(&self) -> CompressionEncoding {
+        match self {
+            GrpcCompression::Gzip => CompressionEncoding::Gzip,
+            GrpcCompression::Zstd => CompressionEncoding::Zstd,
+        }
+    }
+}
+
+/// Length prefix of every gRPC message on the wire.
+const MESSAGE_PREFIX_LEN: u64 = 5;
+
+/// Whether the client and the server send a stream of messages in a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CallKind {
+    Unary,
+    ServerStreaming,
+    ClientStreaming,
+    Bidi,
+}
+
+/// Messages of a response and their encoded size.
+#[derive(Default)]
+struct Received {
+    messages: u64,
+    bytes: u64,
+    /// Response metadata, kept only for checks
+    metadata: HeaderMap,
+    /// Response messages, kept only for checks
+    bodies: Vec<DynamicMessage>,
+}
+
+impl Received {
+    fn add(&mut self, message: DynamicMessage, keep: bool) {
+        self.messages += 1;
+        self.bytes += message.encoded_len() as u64;
+        if keep {
+            self.bodies.push(message);
+        }
+    }
+}
+
+/// The request message, built once unless its JSON has template expressions.
+enum MessageTemplate {
+    Static(DynamicMessage),
+    Dynamic(MethodDescriptor, Template),
+}
+
 impl<'a> GrpcRequester<'a> {
     pub fn new(
         metrics: &'a Arc<Metrics>,
         address: String,
-        proto: String,
+        protos: ProtoSources,
         method: String,
         data: Option<String>,
         timeout: u64,
@@ -45,63 +133,363 @@ impl<'a> GrpcRequester<'a> {
         Self {
             metrics,
             address,
-            proto,
+            protos,
             method,
             data,
             timeout,
+            messages: 1,
+            metadata: MetadataMap::new(),
+            deadline: None,
+            compression: None,
+            templates: true,
+            checks: Vec::new(),
+            tls: Arc::default(),
+            descriptors: Arc::default(),
+            shared_channel: None,
             path_uri: OnceLock::new(),
+            call_kind: OnceLock::new(),
             codec: OnceLock::new(),
             req_msg: OnceLock::new(),
-            client: OnceLock::new(),
             channel: OnceLock::new(),
         }
     }
+
+    pub fn with_tls(mut self, tls: Arc<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Number of messages sent per call by client streaming and
+    /// bidirectional methods, each rendered from the data template.
+    pub fn with_messages(mut self, messages: u64) -> Self {
+        self.messages = messages;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: MetadataMap) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Fails calls that did not complete within `deadline` with
+    /// `DEADLINE_EXCEEDED`.
+    pub fn with_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Option<GrpcCompression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sends the data as it is when `templates` is off.
+    pub fn with_templates(mut self, templates: bool) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    pub fn with_checks(mut self, checks: Vec<Check>) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Shares the descriptors with other workers, so that the proto file is
+    /// compiled or the server reflection service is called only once.
+    pub fn with_descriptors(mut self, descriptors: Arc<OnceCell<DescriptorPool>>) -> Self {
+        self.descriptors = descriptors;
+        self
+    }
+
+    /// Sends the requests over a channel shared with other workers instead of
+    /// a channel of its own.
+    pub fn with_shared_channel(mut self, channel: Option<Arc<SharedClient<Channel>>>) -> Self {
+        self.shared_channel = channel;
+        self
+    }
+
+    async fn load_descriptors(&self, channel: &Channel) -> Result<DescriptorPool, RequestError> {
+        let service = descriptors::split_method(&self.method)
+            .map_err(|e| RequestError::ConfigError(e.to_string()))?
+            .0
+            .to_string();
+
+        descriptors::load(&self.protos, channel, &[service])
+            .await
+            .map_err(|e| RequestError::ConfigError(format!("Failed to load descriptors: {}", e)))
+    }
+
+    fn render_message(
+        &self,
+        template: &MessageTemplate,
+        context: &RequestContext,
+    ) -> Result<DynamicMessage, RequestError> {
+        match template {
+            MessageTemplate::Static(message) => Ok(message.clone()),
+            MessageTemplate::Dynamic(method, template) => {
+                let json_data = template
+                    .render(context)
+                    .map_err(RequestError::InvalidRequest)?;
+                build_request(method, &json_data).map_err(|e| {
+                    RequestError::InvalidRequest(format!("Failed to build request: {}", e))
+                })
+            }
+        }
+    }
+
+    /// Sends the messages with the call of the method's kind.
+    async fn call(
+        &self,
+        client: &mut Grpc<WireCounter<Channel>>,
+        kind: CallKind,
+        messages: Vec<DynamicMessage>,
+        path: PathAndQuery,
+        codec: DynamicCodec,
+        start: Instant,
+    ) -> Result<Received, Status> {
+        let single = |messages: Vec<DynamicMessage>| {
+            messages
+                .into_iter()
+                .next()
+                .ok_or_else(|| Status::internal("no request message"))
+        };
+
+        match kind {
+            CallKind::Unary => {
+                let response = client
+                    .unary(self.new_request(single(messages)?), path, codec)
+                    .await?;
+                Ok(self.received(response))
+            }
+            CallKind::ClientStreaming => {
+                let stream = futures_util::stream::iter(messages);
+                let response = client
+                    .client_streaming(self.new_request(stream), path, codec)
+                    .await?;
+                Ok(self.received(response))
+            }
+            CallKind::ServerStreaming => {
+                let response = client
+                    .server_streaming(self.new_request(single(messages)?), path, codec)
+                    .await?;
+                self.receive(response, start).await
+            }
+            CallKind::Bidi => {
+                let stream = futures_util::stream::iter(messages);
+                let response = client
+                    .streaming(self.new_request(stream), path, codec)
+                    .await?;
+                self.receive(response, start).await
+            }
+        }
+    }
+
+    fn received(&self, response: tonic::Response<DynamicMessage>) -> Received {
+        let keep = !self.checks.is_empty();
+        let (metadata, message, _) = response.into_parts();
+
+        let mut received = Received::default();
+        if keep {
+            received.metadata = metadata.into_headers();
+        }
+        received.add(message, keep);
+        received
+    }
+
+    /// Reads a response stream to its end, recording when its messages
+    /// arrived.
+    async fn receive(
+        &self,
+        response: tonic::Response<Streaming<DynamicMessage>>,
+        start: Instant,
+    ) -> Result<Received, Status> {
+        let keep = !self.checks.is_empty();
+        let (metadata, mut stream, _) = response.into_parts();
+
+        let mut received = Received::default();
+        if keep {
+            received.metadata = metadata.into_headers();
+        }
+        let mut last = None;
+
+        while let Some(message) = stream.message().await? {
+            let now = Instant::now();
+            match last {
+                None => self.record(Timing::FirstMessage, now - start).await,
+                Some(previous) => self.record(Timing::Message, now - previous).await,
+            }
+            last = Some(now);
+
+            received.add(message, keep);
+        }
+
+        Ok(received)
+    }
+
+    /// Evaluates the checks against the response, returning the first one
+    /// that failed.
+    async fn check(&self, kind: CallKind, code: tonic::Code, received: &Received) -> Option<&str> {
+        if self.checks.is_empty() {
+            return None;
+        }
+
+        // Field names as in the proto file, with fields set to their default
+        // values included so that they can be checked too
+        let options = SerializeOptions::new()
+            .skip_default_fields(false)
+            .use_proto_field_name(true);
+        let json = |message: &DynamicMessage| {
+            message
+                .serialize_with_options(serde_json::value::Serializer, &options)
+                .unwrap_or_default()
+        };
+        let message = match kind {
+            _ if code != tonic::Code::Ok => None,
+            CallKind::Unary | CallKind::ClientStreaming => received.bodies.first().map(json),
+            CallKind::ServerStreaming | CallKind::Bidi => {
+                Some(JsonValue::Array(received.bodies.iter().map(json).collect()))
+            }
+        };
+
+        let response = GrpcResponse {
+            code,
+            metadata: &received.metadata,
+            message: message.as_ref(),
+            size: received.bytes as usize,
+        };
+        let mut failed_check = None;
+        for check in &self.checks {
+            let passed = check.evaluate_grpc(&response);
+            self.metrics.record_check(check.name(), passed).await;
+            if !passed && failed_check.is_none() {
+                failed_check = Some(check.name());
+            }
+        }
+
+        failed_check
+    }
+
+    async fn record(&self, timing: Timing, duration: Duration) {
+        self.metrics
+            .record_timing(timing, Metrics::micros(duration))
+            .await;
+    }
+
+    fn new_request<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        *request.metadata_mut() = self.metadata.clone();
+        request.metadata_mut().insert(
+            "te",
+            tonic::metadata::MetadataValue::try_from("trailers").unwrap(),
+        );
+        if let Some(deadline) = self.deadline {
+            request.set_timeout(deadline);
+        }
+        request
+    }
+}
+
+/// Adds `key: value` to the metadata of the calls. Values of binary keys,
+/// which end in `-bin`, are given base64 encoded.
+pub fn insert_metadata(metadata: &mut MetadataMap, key: &str, value: &str) -> Result<(), String> {
+    let key = key.trim().to_ascii_lowercase();
+    let value = value.trim();
+
+    if key.ends_with("-bin") {
+        let name = BinaryMetadataKey::from_bytes(key.as_bytes())
+            .map_err(|_| format!("invalid metadata key '{}'", key))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .or_else(|_| base64::engine::general_purpose::STANDARD_NO_PAD.decode(value))
+            .map_err(|e| format!("value of metadata '{}' is not base64: {}", key, e))?;
+        metadata.append_bin(name, BinaryMetadataValue::from_bytes(&bytes));
+    } else {
+        let name = AsciiMetadataKey::from_bytes(key.as_bytes())
+            .map_err(|_| format!("invalid metadata key '{}'", key))?;
+        let value = AsciiMetadataValue::try_from(value)
+            .map_err(|_| format!("invalid value of metadata '{}'", key))?;
+        metadata.append(name, value);
+    }
+
+    Ok(())
+}
+
+/// Connects a channel to `address`, recording the connection timings in
+/// `metrics`.
+pub async fn connect(
+    address: &str,
+    metrics: &Arc<Metrics>,
+    tls: &TlsConfig,
+    timeout: u64,
+) -> Result<Channel, RequestError> {
+    let endpoint = tonic::transport::Endpoint::from_shared(address.to_string())
+        .map_err(|e| RequestError::ConnectionError(format!("Invalid URI: {}", e)))?;
+
+    // gRPC runs over HTTP/2, which TLS servers only pick through ALPN
+    let connector = TimingConnector::new(Arc::clone(metrics), tls, &["h2"])
+        .map_err(|e| RequestError::ConfigError(format!("Failed to initialise TLS: {}", e)))?;
+
+    endpoint
+        .timeout(Duration::from_secs(timeout))
+        .connect_with_connector(connector)
+        .await
+        .map_err(|e| RequestError::ConnectionError(format!("Failed to connect: {}", e)))
 }
 
 impl<'a> Requester for GrpcRequester<'a> {
     async fn initialize(&self) -> Result<(), crate::requester::error::RequestError> {
-        if self.client.get().is_some() {
+        if self.channel.get().is_some() {
             return Ok(());
         }
 
-        let pool = load_proto(&self.proto).map_err(|e| {
-            RequestError::ConfigError(format!("Failed to load proto '{}': {}", self.proto, e))
-        })?;
+        let connect = || connect(&self.address, self.metrics, &self.tls, self.timeout);
+        let channel = match &self.shared_channel {
+            Some(shared) => shared.client.get_or_try_init(connect).await?.clone(),
+            None => connect().await?,
+        };
+
+        let pool = self
+            .descriptors
+            .get_or_try_init(|| self.load_descriptors(&channel))
+            .await?;
 
-        let method = get_method(&pool, &self.method)
+        let method = descriptors::get_method(pool, &self.method)
             .map_err(|e| RequestError::ConfigError(format!("Failed to get method: {}", e)))?;
 
         let req_msg_val = if let Some(json_data) = &self.data {
-            build_request(&method, json_data).map_err(|e| {
-                RequestError::InvalidRequest(format!("Failed to build request: {}", e))
-            })?
+            let template = if self.templates {
+                json_data.parse::<Template>().map_err(|e| {
+                    RequestError::ConfigError(format!("Invalid template '{}': {}", json_data, e))
+                })?
+            } else {
+                Template::literal(json_data)
+            };
+            match template.as_static() {
+                Some(json_data) => {
+                    MessageTemplate::Static(build_request(&method, json_data).map_err(|e| {
+                        RequestError::InvalidRequest(format!("Failed to build request: {}", e))
+                    })?)
+                }
+                None => MessageTemplate::Dynamic(method.clone(), template),
+            }
         } else {
-            DynamicMessage::new(method.input())
+            MessageTemplate::Static(DynamicMessage::new(method.input()))
         };
 
-        let endpoint = tonic::transport::Endpoint::from_shared(self.address.to_string())
-            .map_err(|e| RequestError::ConnectionError(format!("Invalid URI: {}", e)))?;
-
-        let channel = endpoint
-            .timeout(Duration::from_secs(self.timeout))
-            .connect()
-            .await
-            .map_err(|e| RequestError::ConnectionError(format!("Failed to connect: {}", e)))?;
-
         let path = format!("/{}/{}", method.parent_service().full_name(), method.name());
 
         let path_uri_val: http::Uri = path
             .parse()
             .map_err(|e| RequestError::InvalidRequest(format!("Invalid path: {}", e)))?;
 
-        let mut client_val = Grpc::new(channel.clone());
+        let codec_val = DynamicCodec::new(method.output().clone());
 
-        client_val
-            .ready()
-            .await
-            .map_err(|e| RequestError::ConnectionError(format!("Client not ready: {}", e)))?;
-
-        let codec_val = DynamicCodec::new(method.input().clone(), method.output().clone());
+        let call_kind = match (method.is_client_streaming(), method.is_server_streaming()) {
+            (false, false) => CallKind::Unary,
+            (false, true) => CallKind::ServerStreaming,
+            (true, false) => CallKind::ClientStreaming,
+            (true, true) => CallKind::Bidi,
+        };
 
         if self.channel.set(channel).is_err() {
             return Err(RequestError::InternalError(
@@ -109,15 +497,15 @@ impl<'a> Requester for GrpcRequester<'a> {
             ));
         }
 
-        if self.client.set(client_val).is_err() {
+        if self.path_uri.set(path_uri_val).is_err() {
             return Err(RequestError::InternalError(
-                "Client already set".to_string(),
+                "Path URI already set".to_string(),
             ));
         }
 
-        if self.path_uri.set(path_uri_val).is_err() {
+        if self.call_kind.set(call_kind).is_err() {
             return Err(RequestError::InternalError(
-                "Path URI already set".to_string(),
+                "Call kind already set".to_string(),
             ));
         }
 
@@ -133,29 +521,41 @@ impl<'a> Requester for GrpcRequester<'a> {
 
         Ok(())
     }
-    async fn request(&self) -> Result<(), RequestError> {
-        let stored_client = self.client.get().ok_or(RequestError::InternalError(
+    async fn request(&self, context: &mut RequestContext) -> Result<(), RequestError> {
+        let channel = self.channel.get().ok_or(RequestError::InternalError(
             "Requester not initialised: Missing client".to_string(),
         ))?;
-        let mut client = stored_client.clone();
+
+        // Counts the bytes of this call on the wire, after compression
+        let wire = Arc::new(WireBytes::default());
+        let mut client = Grpc::new(WireCounter::new(channel.clone(), Arc::clone(&wire)));
+        if let Some(compression) = self.compression {
+            client = client
+                .send_compressed(compression.encoding())
+                .accept_compressed(compression.encoding());
+        }
         let path_uri_val = self.path_uri.get().ok_or(RequestError::InternalError(
             "Requester not initialised: Missing path_uri".to_string(),
         ))?;
         let codec = self.codec.get().ok_or(RequestError::InternalError(
             "Requester not initialised: Missing codec".to_string(),
         ))?;
-        let req_msg = self.req_msg.get().ok_or(RequestError::InternalError(
+        let kind = *self.call_kind.get().ok_or(RequestError::InternalError(
+            "Requester not initialised: Missing call kind".to_string(),
+        ))?;
+        let template = self.req_msg.get().ok_or(RequestError::InternalError(
             "Requester not initialised: Missing request message".to_string(),
         ))?;
 
-        let start = std::time::Instant::now();
-
-        let mut request = tonic::Request::new(req_msg.clone());
-
-        request.metadata_mut().insert(
-            "te",
-            tonic::metadata::MetadataValue::try_from("trailers").unwrap(),
-        );
+        // Only request streams carry more than one message
+        let count = match kind {
+            CallKind::ClientStreaming | CallKind::Bidi => self.messages,
+            CallKind::Unary | CallKind::ServerStreaming => 1,
+        };
+        let messages = (0..count)
+            .map(|_| self.render_message(template, context))
+            .collect::<Result<Vec<_>, _>>()?;
+        let request_size: usize = messages.iter().map(Message::encoded_len).sum();
 
         let path_and_query = path_uri_val
             .path_and_query()
@@ -164,96 +564,107 @@ impl<'a> Requester for GrpcRequester<'a> {
             ))?
             .clone();
 
+        // Waiting for a free stream of a shared channel counts towards the
+        // latency, like waiting for a connection
+        let start = Instant::now();
+        let _stream = match &self.shared_channel {
+            Some(shared) => shared.stream().await,
+            None => None,
+        };
+
         client.ready().await.map_err(|e| {
             RequestError::ConnectionError(format!("Client not ready (in request): {}", e))
         })?;
 
-        let response = client
-            .unary(request, path_and_query, codec.clone())
+        let call = self.call(
+            &mut client,
+            kind,
+            messages,
+            path_and_query,
+            codec.clone(),
+            start,
+        );
+
+        // Servers may not enforce grpc-timeout, so the deadline is kept here too
+        let call = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::timeout(deadline, call)
+                    .await
+                    .unwrap_or_else(|_| Err(Status::deadline_exceeded("Deadline exceeded"))),
+                None => call.await,
+            }
+        };
+
+        // The channel timeout ends with the response headers, the whole
+        // stream is bounded here
+        let response = tokio::time::timeout(Duration::from_secs(self.timeout), call)
             .await
-            .map_err(|e| {
-                println!("gRPC error details: {:?}", e);
-                RequestError::GrpcError(format!("gRPC call failed: {}", e))
-            })?;
+            .map_err(|_| RequestError::Timeout)?;
+
+        let (received, status) = match response {
+            Ok(received) => (received, None),
+            Err(status) => {
+                let received = Received {
+                    metadata: status.metadata().clone().into_headers(),
+                    ..Received::default()
+                };
+                (received, Some(status))
+            }
+        };
+        let code = status
+            .as_ref()
+            .map_or(tonic::Code::Ok, |status| status.code());
+        self.metrics.record_grpc_code(code).await;
+
+        let failed_check = self.check(kind, code, &received).await;
 
         let req_duration = start.elapsed();
 
-        let request_size = req_msg.encode_to_vec().len();
-        self.metrics.add_bytes_sent(request_size as u64).await;
+        if kind != CallKind::Unary {
+            self.record(Timing::Stream, req_duration).await;
+        }
 
-        let response_size = response.get_ref().encode_to_vec().len();
-        self.metrics.add_bytes_received(response_size as u64).await;
+        self.metrics.add_bytes_sent(request_size as u64).await;
+        self.metrics.add_messages_sent(count).await;
+        self.metrics.add_bytes_received(received.bytes).await;
+        self.metrics.add_messages_received(received.messages).await;
+
+        if self.compression.is_some() {
+            let compressed =
+                |bytes: u64, messages: u64| bytes.saturating_sub(messages * MESSAGE_PREFIX_LEN);
+            self.metrics
+                .add_compressed_bytes_sent(compressed(wire.sent(), count), request_size as u64)
+                .await;
+            self.metrics
+                .add_compressed_bytes_received(
+                    compressed(wire.received(), received.messages),
+                    received.bytes,
+                )
+                .await;
+        }
 
         self.metrics
-            .record_latency(req_duration.as_micros().try_into().unwrap_or(0))
+            .record_latency(Metrics::micros(req_duration))
             .await;
 
-        Ok(())
-    }
-}
-
-fn load_proto(path: &str) -> Result<DescriptorPool, Box<dyn std::error::Error>> {
-    // Check if it's a compiled descriptor or source file
-    if path.ends_with(".proto") {
-        // Compile the .proto file on the fly
-        let mut pool = DescriptorPool::new();
-
-        // Get the directory containing the .proto file
-        let proto_path = std::path::Path::new(path);
-        let parent_dir = proto_path
-            .parent()
-            .ok_or_else(|| anyhow::anyhow!("Could not determine parent directory"))?;
-
-        // Use protox to parse the .proto file
-        let file_descriptor_set = protox::compile([path], [parent_dir])?;
-
-        for file in file_descriptor_set.file {
-            pool.add_file_descriptor_proto(file)?;
+        // Failed calls are recorded like the others, expecting a code
+        // explicitly allows calls that did not end with OK
+        if let Some(status) = status {
+            if !self.checks.iter().any(Check::is_code) {
+                return Err(RequestError::GrpcStatus(
+                    status.code(),
+                    status.message().to_string(),
+                ));
+            }
         }
-
-        Ok(pool)
-    } else {
-        // Assume it's a compiled descriptor set
-        let bytes = std::fs::read(path)?;
-        let file_descriptor_set = prost_types::FileDescriptorSet::decode(&bytes[..])?;
-
-        let mut pool = DescriptorPool::new();
-        for file in file_descriptor_set.file {
-            pool.add_file_descriptor_proto(file)?;
+        if let Some(check) = failed_check {
+            return Err(RequestError::CheckFailed(check.to_string()));
         }
 
-        Ok(pool)
+        Ok(())
     }
 }
 
-fn get_method<'a>(
-    pool: &'a DescriptorPool,
-    full_method: &str,
-) -> anyhow::Result<prost_reflect::MethodDescriptor> {
-    let (service_name, method_name) = if let Some((svc, method)) = full_method.rsplit_once('/') {
-        (svc.trim(), method.trim())
-    } else if let Some((svc, method)) = full_method.rsplit_once('.') {
-        (svc.trim(), method.trim())
-    } else {
-        return Err(anyhow::anyhow!(
-            "invalid format: expected Service.Method or Service/Method, got: '{}'",
-            full_method
-        ));
-    };
-
-    let service = pool
-        .get_service_by_name(service_name)
-        .ok_or_else(|| anyhow::anyhow!("service not found: {}", service_name))?;
-
-    let methods: Vec<_> = service.methods().collect();
-    let method = methods
-        .into_iter()
-        .find(|m| m.name() == method_name)
-        .ok_or_else(|| anyhow::anyhow!("method not found: {}", method_name))?;
-
-    Ok(method)
-}
-
 fn build_request(method: &MethodDescriptor, json: &str) -> anyhow::Result<DynamicMessage> {
     let json_value: JsonValue = serde_json::from_str(json)?;
 
@@ -265,19 +676,12 @@ fn build_request(method: &MethodDescriptor, json: &str) -> anyhow::Result<Dynami
 // Custom codec for dynamic messages
 #[derive(Debug, Clone)]
 struct DynamicCodec {
-    input_desc: prost_reflect::MessageDescriptor,
     output_desc: prost_reflect::MessageDescriptor,
 }
 
 impl DynamicCodec {
-    fn new(
-        input_desc: prost_reflect::MessageDescriptor,
-        output_desc: prost_reflect::MessageDescriptor,
-    ) -> Self {
-        Self {
-            input_desc,
-            output_desc,
-        }
+    fn new(output_desc: prost_reflect::MessageDescriptor) -> Self {
+        Self { output_desc }
     }
 }
 
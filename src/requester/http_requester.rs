@@ -1,21 +1,74 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, sync::OnceLock, time::Duration};
 
-use crate::requester::error::RequestError;
-use http::StatusCode;
-use reqwest::{header::HeaderMap, Client, Method};
+use bytes::Bytes;
+use http::{
+    header::{
+        HeaderName, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, HOST,
+        LOCATION, PROXY_AUTHORIZATION, TRANSFER_ENCODING,
+    },
+    HeaderMap, Method, Request, StatusCode, Uri, Version,
+};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Body;
+use hyper_util::{
+    client::legacy::Client,
+    rt::{TokioExecutor, TokioTimer},
+};
 
-use crate::metrics::metrics::Metrics;
+use crate::metrics::{metrics::Metrics, timing::Timing};
+use crate::requester::check::{Check, Response};
+use crate::requester::client::{
+    ConnectionOptions, ConnectionReuse, HttpClient, HttpVersion, SharedClient,
+    DEFAULT_MAX_REDIRECTS,
+};
+use crate::requester::connector::{ConnectedAt, TimingConnector};
+use crate::requester::error::RequestError;
+use crate::requester::extract::Extractor;
+use crate::requester::proxy::ProxyConfig;
+use crate::requester::tls::TlsConfig;
 
 use crate::requester::Requester;
+use crate::template::{context::RequestContext, template::Template};
 
 pub struct HttpRequester<'a> {
     metrics: &'a Arc<Metrics>,
     method: Method,
     url: String,
     body: Option<String>,
-    form_params: HashMap<String, String>,
-    client: Client,
-    request_size: u64,
+    form: HashMap<String, String>,
+    headers: HeaderMap,
+    timeout: u64,
+    checks: Vec<Check>,
+    extractors: Vec<(String, Extractor)>,
+    tls: Arc<TlsConfig>,
+    proxy: Arc<ProxyConfig>,
+    connection: ConnectionOptions,
+    templates: bool,
+    /// Client shared with other workers, created by the first one to use it
+    shared_client: Option<Arc<SharedClient<HttpClient>>>,
+
+    template: OnceLock<RequestTemplate>,
+    client: OnceLock<HttpClient>,
+}
+
+/// The request with its templates parsed. Parts without template expressions
+/// are prepared once so that static requests are only cloned.
+struct RequestTemplate {
+    uri: UriTemplate,
+    headers: HeaderMap,
+    header_templates: Vec<(HeaderName, Template)>,
+    body: BodyTemplate,
+}
+
+enum UriTemplate {
+    Static(Uri),
+    Dynamic(Template),
+}
+
+enum BodyTemplate {
+    Static(Bytes),
+    Raw(Template),
+    Form(Vec<(String, Template)>),
 }
 
 impl<'a> HttpRequester<'a> {
@@ -28,95 +81,730 @@ impl<'a> HttpRequester<'a> {
         headers: HeaderMap,
         timeout: u64,
     ) -> Self {
-        let client = Client::builder()
-            .default_headers(headers.clone())
-            .timeout(Duration::from_secs(timeout))
-            .build()
-            .unwrap();
-
-        let mut request_size = 0;
-        if let Some(b) = body.clone() {
-            request_size += b.len() as u64;
-        }
-        for (key, value) in form_params.clone() {
-            request_size += key.len() as u64 + value.len() as u64;
-        }
-        for (key, value) in headers.iter() {
-            request_size += key.as_str().len() as u64 + value.as_bytes().len() as u64;
-        }
-
         Self {
             metrics,
             method,
             url,
             body,
-            form_params,
-            client,
-            request_size,
+            form: form_params,
+            headers,
+            timeout,
+            checks: Vec::new(),
+            extractors: Vec::new(),
+            tls: Arc::default(),
+            proxy: Arc::default(),
+            connection: ConnectionOptions::default(),
+            templates: true,
+            shared_client: None,
+            template: OnceLock::new(),
+            client: OnceLock::new(),
         }
     }
-}
 
-impl<'a> Requester for HttpRequester<'a> {
-    async fn initialize(&self) -> Result<(), RequestError> {
-        Ok(())
+    /// Checks every response has to pass for the request to succeed.
+    pub fn with_checks(mut self, checks: Vec<Check>) -> Self {
+        self.checks = checks;
+        self
     }
 
-    async fn request(&self) -> Result<(), RequestError> {
-        let start = std::time::Instant::now();
+    pub fn with_tls(mut self, tls: Arc<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Proxies to send the requests through.
+    pub fn with_proxy(mut self, proxy: Arc<ProxyConfig>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// HTTP version and connection pool settings of the client.
+    pub fn with_connection(mut self, connection: ConnectionOptions) -> Self {
+        self.connection = connection;
+        self
+    }
 
-        let req_builder = self.client.request(self.method.clone(), self.url.clone());
-        let req_builder = if self.form_params.len() > 0 {
-            req_builder.form(&self.form_params)
-        } else {
-            req_builder
+    /// Sends the URL, headers and body as they are when `templates` is off.
+    pub fn with_templates(mut self, templates: bool) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Sends the requests with a client shared with other workers instead of
+    /// a client of its own.
+    pub fn with_shared_client(mut self, client: Option<Arc<SharedClient<HttpClient>>>) -> Self {
+        self.shared_client = client;
+        self
+    }
+
+    /// Variables to set from every successful response, by name.
+    pub fn with_extractors(mut self, extractors: Vec<(String, Extractor)>) -> Self {
+        self.extractors = extractors;
+        self
+    }
+
+    fn parse_template(&self) -> Result<RequestTemplate, RequestError> {
+        let parse = |source: &str| {
+            if !self.templates {
+                return Ok(Template::literal(source));
+            }
+            source.parse::<Template>().map_err(|e| {
+                RequestError::ConfigError(format!("Invalid template '{}': {}", source, e))
+            })
         };
-        let req_builder = match self.body.clone() {
-            Some(b) => req_builder.body(b.clone()),
-            None => req_builder,
+
+        let uri = match parse(&self.url)?.as_static() {
+            Some(url) => UriTemplate::Static(parse_uri(url)?),
+            None => UriTemplate::Dynamic(parse(&self.url)?),
         };
 
-        let resp = req_builder.send().await.map_err(|e| {
-            if e.is_timeout() {
-                RequestError::Timeout
-            } else {
-                RequestError::Network
+        let mut headers = self.headers.clone();
+        let mut header_templates = Vec::new();
+        for (name, value) in &self.headers {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            let template = parse(value)?;
+            if template.as_static().is_none() {
+                headers.remove(name);
+                header_templates.push((name.clone(), template));
             }
-        })?;
-        let status = resp.status();
+        }
+
+        // An explicit body takes precedence over form parameters
+        let body = match &self.body {
+            Some(body) => {
+                let template = parse(body)?;
+                match template.as_static() {
+                    Some(body) => BodyTemplate::Static(Bytes::from(body.to_string())),
+                    None => BodyTemplate::Raw(template),
+                }
+            }
+            None if !self.form.is_empty() => {
+                headers
+                    .entry(CONTENT_TYPE)
+                    .or_insert(HeaderValue::from_static(
+                        "application/x-www-form-urlencoded",
+                    ));
+                let form = self
+                    .form
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), parse(value)?)))
+                    .collect::<Result<Vec<_>, RequestError>>()?;
+                if form.iter().all(|(_, value)| value.as_static().is_some()) {
+                    let encoded = form_urlencoded::Serializer::new(String::new())
+                        .extend_pairs(self.form.iter())
+                        .finish();
+                    BodyTemplate::Static(Bytes::from(encoded))
+                } else {
+                    BodyTemplate::Form(form)
+                }
+            }
+            None => BodyTemplate::Static(Bytes::new()),
+        };
+
+        Ok(RequestTemplate {
+            uri,
+            headers,
+            header_templates,
+            body,
+        })
+    }
 
-        self.metrics.add_bytes_sent(self.request_size).await;
+    fn build_request(
+        &self,
+        template: &RequestTemplate,
+        context: &RequestContext,
+    ) -> Result<Request<Full<Bytes>>, RequestError> {
+        let uri = match &template.uri {
+            UriTemplate::Static(uri) => uri.clone(),
+            UriTemplate::Dynamic(url) => {
+                parse_uri(&url.render(context).map_err(RequestError::InvalidRequest)?)?
+            }
+        };
+
+        let mut headers = template.headers.clone();
+        for (name, value) in &template.header_templates {
+            let value = value
+                .render(context)
+                .map_err(RequestError::InvalidRequest)?;
+            let value = HeaderValue::from_str(&value).map_err(|e| {
+                RequestError::InvalidRequest(format!("Invalid value of header '{}': {}", name, e))
+            })?;
+            headers.insert(name.clone(), value);
+        }
+
+        let body = match &template.body {
+            BodyTemplate::Static(body) => body.clone(),
+            BodyTemplate::Raw(body) => {
+                Bytes::from(body.render(context).map_err(RequestError::InvalidRequest)?)
+            }
+            BodyTemplate::Form(form) => {
+                let pairs = form
+                    .iter()
+                    .map(|(key, value)| Ok((key.as_str(), value.render(context)?)))
+                    .collect::<Result<Vec<_>, String>>()
+                    .map_err(RequestError::InvalidRequest)?;
+                let encoded = form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(pairs)
+                    .finish();
+                Bytes::from(encoded)
+            }
+        };
+
+        let mut request = Request::new(Full::new(body));
+        *request.method_mut() = self.method.clone();
+        *request.uri_mut() = uri;
+        *request.headers_mut() = headers;
+
+        Ok(request)
+    }
+
+    fn build_client(&self) -> Result<HttpClient, RequestError> {
+        let connection = &self.connection;
+        let connector = TimingConnector::new(
+            Arc::clone(self.metrics),
+            &self.tls,
+            connection.http_version.alpn(),
+        )
+        .map_err(|e| RequestError::ConfigError(format!("Failed to initialise TLS: {}", e)))?
+        .with_proxy(Arc::clone(&self.proxy), self.forwards_to_proxy())
+        .with_connection_limit(connection.pool_size);
+
+        let mut builder = Client::builder(TokioExecutor::new());
+        // Without forcing HTTP/2 a connection uses it only when the server
+        // selected it in the TLS handshake
+        builder
+            .timer(TokioTimer::new())
+            .pool_timer(TokioTimer::new())
+            .http2_only(connection.http_version == HttpVersion::Http2);
+
+        if let Some(idle_timeout) = connection.idle_timeout {
+            builder.pool_idle_timeout(idle_timeout);
+        }
+        match connection.connection_reuse {
+            // Connections that can not be kept idle are closed after the response
+            ConnectionReuse::New => {
+                builder.pool_max_idle_per_host(0);
+            }
+            ConnectionReuse::KeepAlive => {
+                if let Some(pool_size) = connection.pool_size {
+                    builder.pool_max_idle_per_host(pool_size);
+                }
+            }
+        }
+
+        Ok(builder.build(connector))
+    }
+
+    /// Whether plain HTTP requests are forwarded by a proxy rather than
+    /// tunneled, HTTP/2 with prior knowledge always needs a tunnel.
+    fn forwards_to_proxy(&self) -> bool {
+        self.connection.http_version != HttpVersion::Http2
+    }
+
+    /// Sends the request and follows the redirects of its responses, with
+    /// the cookies of the jar for every URL. Returns the last response, the
+    /// values of the extractors are taken from every response of the chain.
+    async fn send(
+        &self,
+        client: &HttpClient,
+        request: Request<Full<Bytes>>,
+        context: &mut RequestContext,
+        extracted: &mut [Option<String>],
+    ) -> Result<(StatusCode, Version, HeaderMap, Bytes), RequestError> {
+        let max_redirects = self
+            .connection
+            .max_redirects
+            .unwrap_or(DEFAULT_MAX_REDIRECTS);
+        let (parts, mut body) = request.into_parts();
+        let mut method = parts.method;
+        let mut uri = parts.uri;
+        let mut headers = parts.headers;
+        let mut redirects = 0;
 
-        if status >= StatusCode::BAD_REQUEST {
-            let req_duration = start.elapsed();
+        loop {
+            let mut request = Request::new(body.clone());
+            *request.method_mut() = method.clone();
+            *request.uri_mut() = uri.clone();
+            *request.headers_mut() = self.hop_headers(&headers, &uri, context);
 
+            self.metrics.add_bytes_sent(request_size(&request)).await;
+            let (status, version, response_headers, response_body) =
+                self.send_one(client, request).await?;
             self.metrics
-                .record_latency(req_duration.as_micros().try_into().unwrap_or(0))
+                .add_bytes_received(response_body.len() as u64)
                 .await;
+            context.cookies.store(&uri, &response_headers);
+
+            // A value set by a redirect, such as the session cookie of a
+            // login, is only in the response that redirects
+            let response = Response {
+                status: status.as_u16(),
+                headers: &response_headers,
+                body: &response_body,
+            };
+            for (value, (_, extractor)) in extracted.iter_mut().zip(&self.extractors) {
+                if let Some(found) = extractor.extract(&response) {
+                    *value = Some(found);
+                }
+            }
+
+            let location = match status {
+                StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT => response_headers
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| resolve_location(&uri, location)),
+                _ => None,
+            };
+            let Some(location) = location.filter(|_| max_redirects > 0) else {
+                return Ok((status, version, response_headers, response_body));
+            };
+            if redirects == max_redirects {
+                return Err(RequestError::RequestFailed(format!(
+                    "Stopped after {} redirects",
+                    max_redirects
+                )));
+            }
+            redirects += 1;
 
-            return Err(RequestError::ServerError(format!(
-                "Service returned {} status code",
-                status
-            )));
+            // Like browsers, a redirected POST and any request answered with
+            // 303 See Other continue as a GET without a body
+            let as_get = match status {
+                StatusCode::SEE_OTHER => method != Method::HEAD,
+                StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND => method == Method::POST,
+                _ => false,
+            };
+            if as_get {
+                method = Method::GET;
+                body = Full::new(Bytes::new());
+                headers.remove(CONTENT_TYPE);
+                headers.remove(CONTENT_LENGTH);
+                headers.remove(TRANSFER_ENCODING);
+            }
+
+            // Credentials are only sent to the origin they were given for
+            if !same_origin(&uri, &location) {
+                headers.remove(AUTHORIZATION);
+                headers.remove(COOKIE);
+                headers.remove(HOST);
+            }
+            uri = location;
         }
+    }
+
+    /// Headers of one request of a redirect chain: the headers of the request
+    /// with the cookies of the jar for `uri` and the credentials of its proxy.
+    fn hop_headers(&self, headers: &HeaderMap, uri: &Uri, context: &RequestContext) -> HeaderMap {
+        let mut headers = headers.clone();
 
-        let body = resp.bytes().await.map_err(|e| {
-            if e.is_timeout() {
-                RequestError::Timeout
+        if let Some(cookies) = context.cookies.header(uri) {
+            // Cookies given as a header are sent along with the ones of the jar
+            let cookies = match headers.get(COOKIE) {
+                Some(header) => {
+                    let mut combined = header.as_bytes().to_vec();
+                    combined.extend_from_slice(b"; ");
+                    combined.extend_from_slice(cookies.as_bytes());
+                    HeaderValue::from_bytes(&combined).unwrap_or(cookies)
+                }
+                None => cookies,
+            };
+            headers.insert(COOKIE, cookies);
+        }
+
+        // Forwarded requests carry the credentials of the proxy, tunneled ones
+        // sent them when the tunnel was opened
+        if uri.scheme_str() == Some("http") && self.forwards_to_proxy() {
+            if let Some(authorization) = self
+                .proxy
+                .for_uri(uri)
+                .and_then(|proxy| proxy.authorization.clone())
+            {
+                headers.insert(PROXY_AUTHORIZATION, authorization);
+            }
+        }
+
+        headers
+    }
+
+    async fn send_one(
+        &self,
+        client: &HttpClient,
+        request: Request<Full<Bytes>>,
+    ) -> Result<(StatusCode, Version, HeaderMap, Bytes), RequestError> {
+        let start = std::time::Instant::now();
+        let response = client.request(request).await.map_err(|e| {
+            if e.is_connect() {
+                RequestError::ConnectionError(error_chain(&e))
             } else {
-                RequestError::Network
+                RequestError::Network(error_chain(&e))
             }
         })?;
+        let (parts, body) = response.into_parts();
 
-        let response_size = body.len() as u64;
-        self.metrics.add_bytes_received(response_size).await;
+        // On a new connection the request was only sent once the connection
+        // was established, DNS, connect and TLS are timed on their own
+        let sent = parts
+            .extensions
+            .get::<ConnectedAt>()
+            .map_or(start, |connected| connected.0.max(start));
+        self.metrics
+            .record_timing(Timing::FirstByte, Metrics::micros(sent.elapsed()))
+            .await;
+
+        let download_start = std::time::Instant::now();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| RequestError::Network(error_chain(&e)))?
+            .to_bytes();
+
+        self.metrics
+            .record_timing(Timing::Download, Metrics::micros(download_start.elapsed()))
+            .await;
+
+        Ok((parts.status, parts.version, parts.headers, body))
+    }
+}
+
+impl<'a> Requester for HttpRequester<'a> {
+    async fn initialize(&self) -> Result<(), RequestError> {
+        if self.client.get().is_some() {
+            return Ok(());
+        }
+
+        let template = self.parse_template()?;
+
+        let client = match &self.shared_client {
+            Some(shared) => shared
+                .client
+                .get_or_try_init(|| async { self.build_client() })
+                .await?
+                .clone(),
+            None => self.build_client()?,
+        };
+
+        if self.template.set(template).is_err() {
+            return Err(RequestError::InternalError(
+                "Template already set".to_string(),
+            ));
+        }
+
+        if self.client.set(client).is_err() {
+            return Err(RequestError::InternalError(
+                "Client already set".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn request(&self, context: &mut RequestContext) -> Result<(), RequestError> {
+        let client = self.client.get().ok_or(RequestError::InternalError(
+            "Requester not initialised: Missing client".to_string(),
+        ))?;
+        let template = self.template.get().ok_or(RequestError::InternalError(
+            "Requester not initialised: Missing template".to_string(),
+        ))?;
+
+        let request = self.build_request(template, context)?;
+        let mut extracted = vec![None; self.extractors.len()];
+
+        // Waiting for a free stream of a shared client counts towards the
+        // latency, like waiting for a connection
+        let start = std::time::Instant::now();
+        let _stream = match &self.shared_client {
+            Some(shared) => shared.stream().await,
+            None => None,
+        };
+
+        // The timeout and the latency cover the redirects that were followed
+        let (status, version, headers, body) = tokio::time::timeout(
+            Duration::from_secs(self.timeout),
+            self.send(client, request, context, &mut extracted),
+        )
+        .await
+        .map_err(|_| RequestError::Timeout)??;
+
+        self.metrics.record_http_status(status.as_u16()).await;
+        self.metrics.record_http_version(version).await;
 
         let req_duration = start.elapsed();
 
         self.metrics
-            .record_latency(req_duration.as_micros().try_into().unwrap_or(0))
+            .record_latency(Metrics::micros(req_duration))
             .await;
 
+        let response = Response {
+            status: status.as_u16(),
+            headers: &headers,
+            body: &body,
+        };
+        let mut failed_check = None;
+        for check in &self.checks {
+            let passed = check.evaluate(&response);
+            self.metrics.record_check(check.name(), passed).await;
+            if !passed && failed_check.is_none() {
+                failed_check = Some(check.name());
+            }
+        }
+
+        // Expecting a status explicitly allows 4xx and 5xx responses
+        if status >= StatusCode::BAD_REQUEST && !self.checks.iter().any(Check::is_status) {
+            return Err(RequestError::HttpStatus(status.as_u16()));
+        }
+        if let Some(check) = failed_check {
+            return Err(RequestError::CheckFailed(check.to_string()));
+        }
+
+        for ((name, extractor), value) in self.extractors.iter().zip(extracted) {
+            let value =
+                value.ok_or_else(|| RequestError::ExtractFailed(extractor.name().to_string()))?;
+            context.set_var(name.clone(), value);
+        }
+
         Ok(())
     }
 }
+
+fn parse_uri(url: &str) -> Result<Uri, RequestError> {
+    url.parse()
+        .map_err(|e| RequestError::ConfigError(format!("Invalid URL '{}': {}", url, e)))
+}
+
+/// Resolves the `Location` of a redirect against the URL it redirects from.
+pub fn resolve_location(base: &Uri, location: &str) -> Option<Uri> {
+    let location = location.split('#').next().unwrap_or_default();
+    let scheme = base.scheme_str()?;
+    let authority = base.authority()?;
+
+    let resolved = if location.contains("://") {
+        location.to_string()
+    } else if let Some(rest) = location.strip_prefix("//") {
+        format!("{}://{}", scheme, rest)
+    } else if location.starts_with('/') {
+        format!("{}://{}{}", scheme, authority, location)
+    } else if let Some(query) = location.strip_prefix('?') {
+        format!("{}://{}{}?{}", scheme, authority, base.path(), query)
+    } else {
+        let path = base.path();
+        let dir = &path[..path.rfind('/').map_or(0, |slash| slash + 1)];
+        format!("{}://{}{}{}", scheme, authority, dir, location)
+    };
+
+    resolved.parse().ok()
+}
+
+fn same_origin(a: &Uri, b: &Uri) -> bool {
+    a.scheme() == b.scheme() && a.host() == b.host() && a.port_u16() == b.port_u16()
+}
+
+/// Size of the body and the headers of the request.
+fn request_size(request: &Request<Full<Bytes>>) -> u64 {
+    let body = request.body().size_hint().exact().unwrap_or(0);
+    let headers: u64 = request
+        .headers()
+        .iter()
+        .map(|(key, value)| key.as_str().len() as u64 + value.as_bytes().len() as u64)
+        .sum();
+
+    body + headers
+}
+
+/// Joins an error with its sources, the top level error of the client alone
+/// does not tell what went wrong.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Starts a server that redirects `/hops/N` to `/hops/N-1`, `/see-other`,
+    /// `/found` and `/temporary` to `/hops/0` with 303, 302 and 307, and
+    /// answers `/hops/0` with the method and the body of the request.
+    async fn redirect_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let mut request = String::from_utf8(request).unwrap();
+                let length = request
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |length| length.parse().unwrap());
+                while request.len() < request.find("\r\n\r\n").unwrap() + 4 + length {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+                }
+                let (head, body) = request.split_once("\r\n\r\n").unwrap();
+                let mut request_line = head.split(' ');
+                let method = request_line.next().unwrap();
+                let path = request_line.next().unwrap();
+
+                let (status, location) = match path {
+                    "/hops/0" => ("200 OK", None),
+                    "/see-other" => ("303 See Other", Some("/hops/0".to_string())),
+                    "/found" => ("302 Found", Some("/hops/0".to_string())),
+                    "/temporary" => ("307 Temporary Redirect", Some("/hops/0".to_string())),
+                    path => {
+                        let hops: u32 = path.trim_start_matches("/hops/").parse().unwrap();
+                        ("302 Found", Some(format!("/hops/{}", hops - 1)))
+                    }
+                };
+                let content = format!("{} {}", method, body);
+                let response = match location {
+                    Some(location) => format!(
+                        "HTTP/1.1 {}\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status, location
+                    ),
+                    None => format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        content.len(),
+                        content
+                    ),
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        format!("http://{}", address)
+    }
+
+    async fn send(
+        url: String,
+        method: Method,
+        max_redirects: usize,
+    ) -> Result<(StatusCode, Version, HeaderMap, Bytes), RequestError> {
+        let metrics = Arc::new(Metrics::new());
+        let requester = HttpRequester::new(
+            &metrics,
+            method.clone(),
+            url.clone(),
+            None,
+            HashMap::new(),
+            HeaderMap::new(),
+            5,
+        )
+        .with_connection(ConnectionOptions {
+            max_redirects: Some(max_redirects),
+            ..ConnectionOptions::default()
+        });
+        requester.initialize().await.unwrap();
+
+        let mut request = Request::new(Full::new(Bytes::from("data")));
+        *request.method_mut() = method;
+        *request.uri_mut() = url.parse().unwrap();
+
+        requester
+            .send(
+                requester.client.get().unwrap(),
+                request,
+                &mut RequestContext::new(0, None),
+                &mut [],
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_redirects() {
+        let server = redirect_server().await;
+        // (hops, max redirects, status of the last response or None when the
+        // request fails), without redirects the first response is returned
+        let cases = [
+            (0, 3, Some(StatusCode::OK)),
+            (3, 3, Some(StatusCode::OK)),
+            (4, 3, None),
+            (1, 0, Some(StatusCode::FOUND)),
+        ];
+
+        for (hops, max_redirects, expected) in cases {
+            let result = send(
+                format!("{}/hops/{}", server, hops),
+                Method::GET,
+                max_redirects,
+            )
+            .await;
+
+            match (result, expected) {
+                (Ok((status, ..)), Some(expected)) => assert_eq!(status, expected, "{} hops", hops),
+                (Err(RequestError::RequestFailed(message)), None) => {
+                    assert_eq!(
+                        message,
+                        format!("Stopped after {} redirects", max_redirects)
+                    )
+                }
+                (result, _) => panic!("{} hops: unexpected {:?}", hops, result.map(|r| r.0)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn redirects_posts_as_gets() {
+        let server = redirect_server().await;
+        // (path, method, method and body of the redirected request)
+        let cases = [
+            ("/see-other", Method::POST, "GET "),
+            ("/see-other", Method::PUT, "GET "),
+            ("/found", Method::POST, "GET "),
+            ("/found", Method::PUT, "PUT data"),
+            ("/temporary", Method::POST, "POST data"),
+        ];
+
+        for (path, method, expected) in cases {
+            let (status, _, _, body) = send(format!("{}{}", server, path), method.clone(), 5)
+                .await
+                .unwrap();
+
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body, expected, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn resolves_locations() {
+        let base: Uri = "http://example.com:8080/a/b?x=1".parse().unwrap();
+        let cases = [
+            ("/c", "http://example.com:8080/c"),
+            ("c", "http://example.com:8080/a/c"),
+            ("?y=2", "http://example.com:8080/a/b?y=2"),
+            ("//other.com/d", "http://other.com/d"),
+            ("https://other.com/e#top", "https://other.com/e"),
+        ];
+
+        for (location, expected) in cases {
+            assert_eq!(
+                resolve_location(&base, location).unwrap().to_string(),
+                expected,
+                "{}",
+                location
+            );
+        }
+    }
+}
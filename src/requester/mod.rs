@@ -1,13 +1,28 @@
+pub mod any_requester;
+pub mod check;
+pub mod client;
+pub mod connector;
+pub mod cookie;
+pub mod descriptors;
 pub mod error;
+pub mod extract;
 pub mod grpc_requester;
 pub mod http_requester;
 pub mod params;
+pub mod proxy;
+pub mod reflection;
+pub mod scenario_requester;
+pub mod tls;
 pub mod websocket_requester;
+pub mod wire;
 
 use crate::requester::error::RequestError;
+use crate::template::context::RequestContext;
 
 #[allow(async_fn_in_trait)]
 pub trait Requester {
     async fn initialize(&self) -> Result<(), RequestError>;
-    async fn request(&self) -> Result<(), RequestError>;
+    /// Sends a single request, rendering its templates with `context` and
+    /// storing the values extracted from the response in it.
+    async fn request(&self, context: &mut RequestContext) -> Result<(), RequestError>;
 }
@@ -1,7 +1,13 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
-use reqwest::Method;
+use http::Method;
+use tonic::metadata::MetadataMap;
 
+use crate::requester::{
+    check::Check, descriptors::ProtoSources, extract::Extractor, grpc_requester::GrpcCompression,
+};
+
+#[derive(Debug, Clone)]
 pub enum RequestParams {
     Http(HttpParams),
     Grpc(GrpcParams),
@@ -9,11 +15,35 @@ pub enum RequestParams {
 }
 
 impl RequestParams {
-    pub(crate) fn clone(&self) -> RequestParams {
+    /// Sources of the templates of the request.
+    pub fn templates(&self) -> Vec<&str> {
+        match self {
+            RequestParams::Http(params) => {
+                let mut templates = vec![params.url.as_str()];
+                templates.extend(
+                    params
+                        .headers
+                        .values()
+                        .filter_map(|value| value.to_str().ok()),
+                );
+                templates.extend(params.body.as_deref());
+                templates.extend(params.form.values().map(String::as_str));
+                templates
+            }
+            RequestParams::Grpc(params) => params.data.as_deref().into_iter().collect(),
+            RequestParams::Websocket(params) => vec![params.data.as_str()],
+        }
+    }
+
+    /// Names of the variables the request sets from its responses.
+    pub fn extracted(&self) -> Vec<&str> {
         match self {
-            RequestParams::Http(params) => RequestParams::Http(params.clone()),
-            RequestParams::Grpc(params) => RequestParams::Grpc(params.clone()),
-            RequestParams::Websocket(params) => RequestParams::Websocket(params.clone()),
+            RequestParams::Http(params) => params
+                .extract
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect(),
+            RequestParams::Grpc(_) | RequestParams::Websocket(_) => Vec::new(),
         }
     }
 }
@@ -22,17 +52,27 @@ impl RequestParams {
 pub struct HttpParams {
     pub url: String,
     pub method: Method,
-    pub headers: reqwest::header::HeaderMap,
+    pub headers: http::header::HeaderMap,
     pub body: Option<String>,
     pub form: HashMap<String, String>,
+    pub checks: Vec<Check>,
+    /// Variables set from every response for the following requests
+    pub extract: Vec<(String, Extractor)>,
 }
 
 #[derive(Debug, Clone)]
 pub struct GrpcParams {
     pub address: String,
-    pub proto: String,
+    /// Proto files and descriptor sets, none to use server reflection
+    pub protos: ProtoSources,
     pub method: String,
     pub data: Option<String>,
+    /// Messages sent per call by client streaming and bidirectional methods
+    pub messages: u64,
+    pub metadata: MetadataMap,
+    pub deadline: Option<Duration>,
+    pub compression: Option<GrpcCompression>,
+    pub checks: Vec<Check>,
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,197 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Context, Result};
+use http::uri::PathAndQuery;
+use prost::Message;
+use prost_reflect::DescriptorPool;
+use prost_types::FileDescriptorProto;
+use tonic::{client::Grpc, transport::Channel, Code};
+use tonic_prost::ProstCodec;
+use tonic_reflection::pb::v1::{
+    server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+    ServerReflectionRequest, ServerReflectionResponse,
+};
+
+/// Methods of the reflection service in the order they are tried. Both
+/// versions have the same messages, so the v1 ones are used for either.
+const REFLECTION_METHODS: [&str; 2] = [
+    "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo",
+    "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo",
+];
+
+/// Client of the gRPC server reflection service, which describes the services
+/// of a server so that they can be called without their proto files.
+pub struct ReflectionClient {
+    grpc: Grpc<Channel>,
+    /// Reflection method the server implements, once known
+    method: Option<PathAndQuery>,
+}
+
+impl ReflectionClient {
+    pub fn new(channel: Channel) -> Self {
+        ReflectionClient {
+            grpc: Grpc::new(channel),
+            method: None,
+        }
+    }
+
+    /// Names of the services of the server.
+    pub async fn list_services(&mut self) -> Result<Vec<String>> {
+        let responses = self
+            .call(vec![MessageRequest::ListServices(String::new())])
+            .await?;
+
+        let mut services = Vec::new();
+        for response in responses {
+            match response {
+                MessageResponse::ListServicesResponse(list) => {
+                    services.extend(list.service.into_iter().map(|service| service.name))
+                }
+                MessageResponse::ErrorResponse(error) => {
+                    return Err(anyhow!("failed to list services: {}", error.error_message))
+                }
+                _ => return Err(anyhow!("unexpected reflection response")),
+            }
+        }
+
+        Ok(services)
+    }
+
+    /// Builds a pool from the files that define `symbols`, such as services
+    /// or messages, and the files they import.
+    pub async fn descriptors(&mut self, symbols: &[String]) -> Result<DescriptorPool> {
+        let mut files: BTreeMap<String, FileDescriptorProto> = BTreeMap::new();
+        let mut requests: Vec<MessageRequest> = symbols
+            .iter()
+            .map(|symbol| MessageRequest::FileContainingSymbol(symbol.clone()))
+            .collect();
+
+        // Servers usually send the imported files along, the ones they do
+        // not are asked for by name
+        while !requests.is_empty() {
+            let asked: Vec<String> = requests
+                .iter()
+                .map(|request| match request {
+                    MessageRequest::FileContainingSymbol(name)
+                    | MessageRequest::FileByFilename(name) => name.clone(),
+                    _ => String::new(),
+                })
+                .collect();
+            let known = files.len();
+
+            for response in self.call(requests).await? {
+                match response {
+                    MessageResponse::FileDescriptorResponse(response) => {
+                        for bytes in response.file_descriptor_proto {
+                            let file = FileDescriptorProto::decode(bytes.as_slice())
+                                .context("invalid file descriptor from server reflection")?;
+                            files.entry(file.name().to_string()).or_insert(file);
+                        }
+                    }
+                    MessageResponse::ErrorResponse(error) => {
+                        return Err(anyhow!("server reflection: {}", error.error_message))
+                    }
+                    _ => return Err(anyhow!("unexpected reflection response")),
+                }
+            }
+
+            // A server that keeps answering without the asked for files
+            // would otherwise be asked forever
+            if files.len() == known {
+                return Err(anyhow!(
+                    "server reflection returned no new files for {}",
+                    asked.join(", ")
+                ));
+            }
+
+            let missing: BTreeSet<&String> = files
+                .values()
+                .flat_map(|file| &file.dependency)
+                .filter(|dependency| !files.contains_key(*dependency))
+                .collect();
+            requests = missing
+                .into_iter()
+                .map(|name| MessageRequest::FileByFilename(name.clone()))
+                .collect();
+        }
+
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_protos(files.into_values())
+            .context("invalid descriptors from server reflection")?;
+
+        Ok(pool)
+    }
+
+    /// Sends the requests over one reflection stream and returns the
+    /// responses in the same order.
+    async fn call(&mut self, requests: Vec<MessageRequest>) -> Result<Vec<MessageResponse>> {
+        let methods: Vec<PathAndQuery> = match &self.method {
+            Some(method) => vec![method.clone()],
+            None => REFLECTION_METHODS
+                .iter()
+                .map(|method| PathAndQuery::from_static(method))
+                .collect(),
+        };
+
+        for method in methods {
+            match self.call_method(method.clone(), &requests).await {
+                Ok(responses) => {
+                    self.method = Some(method);
+                    return Ok(responses);
+                }
+                Err(status) if status.code() == Code::Unimplemented => continue,
+                Err(status) => {
+                    return Err(anyhow!(
+                        "server reflection failed: {}: {}",
+                        status.code(),
+                        status.message()
+                    ))
+                }
+            }
+        }
+
+        Err(anyhow!(
+            "server does not support reflection, pass the proto file with --proto"
+        ))
+    }
+
+    async fn call_method(
+        &mut self,
+        method: PathAndQuery,
+        requests: &[MessageRequest],
+    ) -> Result<Vec<MessageResponse>, tonic::Status> {
+        let requests: Vec<ServerReflectionRequest> = requests
+            .iter()
+            .map(|request| ServerReflectionRequest {
+                host: String::new(),
+                message_request: Some(request.clone()),
+            })
+            .collect();
+
+        self.grpc
+            .ready()
+            .await
+            .map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+
+        let codec: ProstCodec<ServerReflectionRequest, ServerReflectionResponse> =
+            ProstCodec::default();
+        let mut stream = self
+            .grpc
+            .streaming(
+                tonic::Request::new(futures_util::stream::iter(requests)),
+                method,
+                codec,
+            )
+            .await?
+            .into_inner();
+
+        let mut responses = Vec::new();
+        while let Some(response) = stream.message().await? {
+            if let Some(response) = response.message_response {
+                responses.push(response);
+            }
+        }
+
+        Ok(responses)
+    }
+}
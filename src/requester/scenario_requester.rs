@@ -0,0 +1,114 @@
+use rand::distr::{weighted::WeightedIndex, Distribution};
+
+use crate::metrics::tagged::TaggedMetrics;
+use crate::requester::any_requester::AnyRequester;
+use crate::requester::client::{ClientOptions, RequestClients};
+use crate::requester::error::RequestError;
+use crate::requester::Requester;
+use crate::scenario::scenario::{Flow, ScenarioRequest};
+use crate::template::context::RequestContext;
+
+/// Sends the requests of the scenario an iteration consists of, depending on
+/// the flow, and records the outcome of each in the metrics of that request.
+pub struct ScenarioRequester<'a> {
+    tagged: &'a [TaggedMetrics],
+    requesters: Vec<AnyRequester<'a>>,
+    weights: WeightedIndex<u32>,
+    flow: Flow,
+}
+
+impl<'a> ScenarioRequester<'a> {
+    /// `tagged` holds the metrics of every request and `clients` its shared
+    /// clients, in the order of `requests`.
+    pub fn new(
+        requests: &[ScenarioRequest],
+        tagged: &'a [TaggedMetrics],
+        clients: &[RequestClients],
+        flow: Flow,
+        options: &ClientOptions,
+        worker_id: u64,
+    ) -> Self {
+        ScenarioRequester {
+            tagged,
+            requesters: requests
+                .iter()
+                .zip(tagged)
+                .zip(clients)
+                .map(|((request, tagged), clients)| {
+                    AnyRequester::new(
+                        &tagged.metrics,
+                        request.params.clone(),
+                        options,
+                        clients,
+                        worker_id,
+                    )
+                })
+                .collect(),
+            // Scenarios reject requests without a positive weight
+            weights: WeightedIndex::new(requests.iter().map(|request| request.weight))
+                .expect("scenario has no requests"),
+            flow,
+        }
+    }
+
+    async fn request_one(
+        &self,
+        index: usize,
+        context: &mut RequestContext,
+    ) -> Result<(), RequestError> {
+        let result = self.requesters[index].request(context).await;
+
+        let metrics = &self.tagged[index].metrics;
+        metrics.increment_total_requests().await;
+        match &result {
+            Ok(_) => {
+                metrics.increment_successful_requests().await;
+            }
+            Err(err) => {
+                metrics.increment_failed_requests().await;
+                metrics.record_error(err).await;
+            }
+        };
+
+        result
+    }
+}
+
+impl Requester for ScenarioRequester<'_> {
+    async fn initialize(&self) -> Result<(), RequestError> {
+        let mut result = Ok(());
+
+        // Initialise every requester even if one of them fails and return
+        // the first failure
+        for requester in &self.requesters {
+            if let Err(err) = requester.initialize().await {
+                result = result.and(Err(err));
+            }
+        }
+
+        result
+    }
+
+    async fn request(&self, context: &mut RequestContext) -> Result<(), RequestError> {
+        match self.flow {
+            Flow::Weighted => {
+                let index = if self.requesters.len() > 1 {
+                    self.weights.sample(&mut rand::rng())
+                } else {
+                    0
+                };
+
+                self.request_one(index, context).await
+            }
+            Flow::Sequence => {
+                // Later steps usually depend on values extracted by the
+                // earlier ones, so they are not sent after a failure
+                for index in 0..self.requesters.len() {
+                    self.request_one(index, context).await?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
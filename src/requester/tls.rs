@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Args;
+use native_tls::{Certificate, Identity, Protocol, TlsConnector};
+use serde::Deserialize;
+
+/// TLS settings shared by the HTTP, gRPC and WebSocket requesters.
+#[derive(Args, Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsOptions {
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "PEM file with CA certificates to trust in addition to the system ones"
+    )]
+    pub cacert: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "key",
+        help = "PEM file with the client certificate chain for mutual TLS"
+    )]
+    pub cert: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "cert",
+        help = "PEM file with the PKCS#8 private key of the client certificate"
+    )]
+    pub key: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Server name sent in the TLS handshake and verified against the certificate [default: host of the URL]"
+    )]
+    pub sni: Option<String>,
+
+    #[arg(
+        long = "tls-min-version",
+        value_name = "VERSION",
+        help = "Lowest TLS version to negotiate: 1.0, 1.1 or 1.2"
+    )]
+    pub min_version: Option<TlsVersion>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Skip verification of server certificates and host names"
+    )]
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum TlsVersion {
+    Tls10,
+    Tls11,
+    Tls12,
+}
+
+impl FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.0" => Ok(TlsVersion::Tls10),
+            "1.1" => Ok(TlsVersion::Tls11),
+            "1.2" => Ok(TlsVersion::Tls12),
+            // native-tls can not require TLS 1.3 on every platform, 1.3 is
+            // still negotiated when both sides support it
+            "1.3" => Err(
+                "TLS 1.3 can not be required as the minimum version, use 1.2 to allow only 1.2 and 1.3"
+                    .to_string(),
+            ),
+            other => Err(format!(
+                "invalid TLS version '{}', expected 1.0, 1.1 or 1.2",
+                other
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for TlsVersion {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// TLS settings with the certificates loaded, so that every connection does
+/// not read them again.
+#[derive(Clone, Default)]
+pub struct TlsConfig {
+    certificates: Vec<Certificate>,
+    identity: Option<Identity>,
+    sni: Option<String>,
+    min_version: Option<Protocol>,
+    insecure: bool,
+    /// Connectors by offered protocols. Building one loads the system
+    /// certificates, which is too slow to repeat for every worker.
+    connectors: Arc<Mutex<HashMap<Vec<String>, TlsConnector>>>,
+}
+
+impl TlsOptions {
+    /// Takes the settings of `other` that are set, resolving its paths
+    /// against `base_dir`.
+    pub fn merge(&mut self, other: &TlsOptions, base_dir: &Path) {
+        if let Some(cacert) = &other.cacert {
+            self.cacert = Some(base_dir.join(cacert));
+        }
+        if let Some(cert) = &other.cert {
+            self.cert = Some(base_dir.join(cert));
+        }
+        if let Some(key) = &other.key {
+            self.key = Some(base_dir.join(key));
+        }
+        if other.sni.is_some() {
+            self.sni.clone_from(&other.sni);
+        }
+        if other.min_version.is_some() {
+            self.min_version = other.min_version;
+        }
+        self.insecure |= other.insecure;
+    }
+}
+
+impl TlsConfig {
+    pub fn load(options: &TlsOptions) -> Result<Self> {
+        let certificates = match &options.cacert {
+            Some(path) => {
+                let pem = String::from_utf8(read(path)?)
+                    .with_context(|| format!("'{}' is not a PEM file", path.display()))?;
+                let certificates = pem_blocks(&pem, "CERTIFICATE")
+                    .into_iter()
+                    .map(|block| Certificate::from_pem(block.as_bytes()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("invalid CA certificate in '{}'", path.display()))?;
+                if certificates.is_empty() {
+                    return Err(anyhow!("no certificates in '{}'", path.display()));
+                }
+                certificates
+            }
+            None => Vec::new(),
+        };
+
+        let identity = match (&options.cert, &options.key) {
+            (Some(cert), Some(key)) => Some(
+                Identity::from_pkcs8(&read(cert)?, &read(key)?)
+                    .context("invalid client certificate or key")?,
+            ),
+            (None, None) => None,
+            _ => return Err(anyhow!("a client certificate needs both --cert and --key")),
+        };
+
+        Ok(TlsConfig {
+            certificates,
+            identity,
+            sni: options.sni.clone(),
+            min_version: options.min_version.map(|version| match version {
+                TlsVersion::Tls10 => Protocol::Tlsv10,
+                TlsVersion::Tls11 => Protocol::Tlsv11,
+                TlsVersion::Tls12 => Protocol::Tlsv12,
+            }),
+            insecure: options.insecure,
+            connectors: Arc::default(),
+        })
+    }
+
+    /// Builds a connector that offers the `alpn` protocols, such as `h2`.
+    pub fn connector(&self, alpn: &[&str]) -> Result<TlsConnector, native_tls::Error> {
+        let key = alpn.iter().map(|protocol| protocol.to_string()).collect();
+        let mut connectors = self.connectors.lock().unwrap();
+        if let Some(connector) = connectors.get(&key) {
+            return Ok(connector.clone());
+        }
+
+        let mut builder = TlsConnector::builder();
+
+        for certificate in &self.certificates {
+            builder.add_root_certificate(certificate.clone());
+        }
+        if let Some(identity) = &self.identity {
+            builder.identity(identity.clone());
+        }
+        if !alpn.is_empty() {
+            builder.request_alpns(alpn);
+        }
+
+        let connector = builder
+            .min_protocol_version(self.min_version)
+            .danger_accept_invalid_certs(self.insecure)
+            .danger_accept_invalid_hostnames(self.insecure)
+            .build()?;
+        connectors.insert(key, connector.clone());
+
+        Ok(connector)
+    }
+
+    /// Server name to send instead of the host of the URL.
+    pub fn sni(&self) -> Option<&str> {
+        self.sni.as_deref()
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("failed to read '{}'", path.display()))
+}
+
+/// Blocks of a PEM bundle with the given label, each with its markers.
+fn pem_blocks<'a>(pem: &'a str, label: &str) -> Vec<&'a str> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+
+    pem.match_indices(&begin)
+        .filter_map(|(start, _)| {
+            let length = pem[start..].find(&end)? + end.len();
+            Some(&pem[start..start + length])
+        })
+        .collect()
+}
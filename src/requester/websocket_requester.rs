@@ -1,40 +1,55 @@
 use std::sync::Arc;
 use std::sync::OnceLock;
-use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 
 use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio_tungstenite::{client_async, WebSocketStream};
 use tungstenite::Message;
 use tungstenite::Utf8Bytes;
 
+use crate::requester::connector::{MaybeTlsStream, TimingConnector};
 use crate::requester::error::RequestError;
+use crate::requester::tls::TlsConfig;
 
 use crate::metrics::metrics::Metrics;
 
 use crate::requester::Requester;
+use crate::template::{context::RequestContext, template::Template};
 
 pub struct WebsocketRequester<'a> {
     metrics: &'a Arc<Metrics>,
     url: String,
     data: String,
-    request_size: u64,
-    writer: OnceLock<Mutex<SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>>>,
+    tls: Arc<TlsConfig>,
+    templates: bool,
+    template: OnceLock<Template>,
+    writer: OnceLock<Mutex<SplitSink<WebSocketStream<MaybeTlsStream>, Message>>>,
 }
 
 impl<'a> WebsocketRequester<'a> {
     pub fn new(metrics: &'a Arc<Metrics>, url: String, data: String) -> Self {
-        let request_size = data.clone().len() as u64;
-
         Self {
             metrics,
             url,
             data,
-            request_size,
+            tls: Arc::default(),
+            templates: true,
+            template: OnceLock::new(),
             writer: OnceLock::new(),
         }
     }
+
+    pub fn with_tls(mut self, tls: Arc<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Sends the data as it is when `templates` is off.
+    pub fn with_templates(mut self, templates: bool) -> Self {
+        self.templates = templates;
+        self
+    }
 }
 
 impl<'a> Requester for WebsocketRequester<'a> {
@@ -43,12 +58,34 @@ impl<'a> Requester for WebsocketRequester<'a> {
             return Ok(());
         }
 
-        let (ws_stream, _) = connect_async(self.url.clone()).await.map_err(|e| {
-            RequestError::ConfigError(format!("Failed to connect to {}: {}", self.url.clone(), e))
+        let template = if self.templates {
+            self.data.parse::<Template>().map_err(|e| {
+                RequestError::ConfigError(format!("Invalid template '{}': {}", self.data, e))
+            })?
+        } else {
+            Template::literal(&self.data)
+        };
+
+        let uri: http::Uri = self
+            .url
+            .parse()
+            .map_err(|e| RequestError::ConfigError(format!("Invalid URL '{}': {}", self.url, e)))?;
+        let connector = TimingConnector::new(Arc::clone(self.metrics), &self.tls, &[])
+            .map_err(|e| RequestError::ConfigError(format!("Failed to initialise TLS: {}", e)))?;
+
+        let stream = connector.connect_stream(&uri).await.map_err(|e| {
+            RequestError::ConnectionError(format!("Failed to connect to {}: {}", self.url, e))
+        })?;
+        let (ws_stream, _) = client_async(self.url.as_str(), stream).await.map_err(|e| {
+            RequestError::ConfigError(format!("Failed to connect to {}: {}", self.url, e))
         })?;
 
         let (write, mut read) = ws_stream.split();
 
+        self.template
+            .set(template)
+            .map_err(|_| RequestError::InternalError("Template already set".to_string()))?;
+
         self.writer
             .set(Mutex::new(write))
             .map_err(|_| RequestError::InternalError("Writer already set".to_string()))?;
@@ -56,11 +93,9 @@ impl<'a> Requester for WebsocketRequester<'a> {
         let metrics = self.metrics.clone();
         tokio::spawn(async move {
             while let Some(msg) = read.next().await {
-                if let Ok(msg) = msg {
-                    if let Message::Text(text) = msg {
-                        let response_size = text.len() as u64;
-                        metrics.add_bytes_received(response_size).await;
-                    }
+                if let Ok(Message::Text(text)) = msg {
+                    let response_size = text.len() as u64;
+                    metrics.add_bytes_received(response_size).await;
                 }
             }
         });
@@ -68,26 +103,33 @@ impl<'a> Requester for WebsocketRequester<'a> {
         Ok(())
     }
 
-    async fn request(&self) -> Result<(), RequestError> {
+    async fn request(&self, context: &mut RequestContext) -> Result<(), RequestError> {
         let writer = self.writer.get().ok_or(RequestError::InternalError(
             "Requester not initialised: Missing writer".to_string(),
         ))?;
+        let template = self.template.get().ok_or(RequestError::InternalError(
+            "Requester not initialised: Missing template".to_string(),
+        ))?;
+
+        let data = template
+            .render(context)
+            .map_err(RequestError::InvalidRequest)?;
 
         let mut writer = writer.lock().await;
 
         let start = std::time::Instant::now();
 
-        self.metrics.add_bytes_sent(self.request_size).await;
+        self.metrics.add_bytes_sent(data.len() as u64).await;
 
         writer
-            .send(Message::Text(Utf8Bytes::from(self.data.clone())))
+            .send(Message::Text(Utf8Bytes::from(data)))
             .await
             .map_err(|e| RequestError::InternalError(e.to_string()))?;
 
         let req_duration = start.elapsed();
 
         self.metrics
-            .record_latency(req_duration.as_micros().try_into().unwrap_or(0))
+            .record_latency(Metrics::micros(req_duration))
             .await;
 
         Ok(())
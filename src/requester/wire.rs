@@ -0,0 +1,121 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use tonic::body::Body as GrpcBody;
+
+/// Bytes of gRPC frames sent and received in a call, as they went over the
+/// connection, so after compression.
+#[derive(Debug, Default)]
+pub struct WireBytes {
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl WireBytes {
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+}
+
+/// Service around a gRPC channel that counts the bytes of the request and
+/// response bodies of its calls.
+#[derive(Clone)]
+pub struct WireCounter<S> {
+    inner: S,
+    bytes: Arc<WireBytes>,
+}
+
+impl<S> WireCounter<S> {
+    pub fn new(inner: S, bytes: Arc<WireBytes>) -> Self {
+        WireCounter { inner, bytes }
+    }
+}
+
+impl<S> tower::Service<http::Request<GrpcBody>> for WireCounter<S>
+where
+    S: tower::Service<http::Request<GrpcBody>, Response = http::Response<GrpcBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<CountingBody<GrpcBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<GrpcBody>) -> Self::Future {
+        let sent = Arc::clone(&self.bytes);
+        let request = request.map(|body| GrpcBody::new(CountingBody::new(body, sent, true)));
+
+        let received = Arc::clone(&self.bytes);
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            Ok(response.map(|body| CountingBody::new(body, received, false)))
+        })
+    }
+}
+
+/// Body that adds the length of its data frames to the sent or received
+/// bytes of the call.
+pub struct CountingBody<B> {
+    inner: B,
+    bytes: Arc<WireBytes>,
+    sent: bool,
+}
+
+impl<B> CountingBody<B> {
+    fn new(inner: B, bytes: Arc<WireBytes>, sent: bool) -> Self {
+        CountingBody { inner, bytes, sent }
+    }
+}
+
+impl<B> Body for CountingBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                let counter = if self.sent {
+                    &self.bytes.sent
+                } else {
+                    &self.bytes.received
+                };
+                counter.fetch_add(data.len() as u64, Ordering::Relaxed);
+            }
+        }
+
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{parser::ValueSource, ArgMatches};
+use http::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Method,
+};
+use serde::Deserialize;
+
+use crate::{
+    commands::Cli,
+    metrics::tagged::Tags,
+    report::threshold::Threshold,
+    requester::{
+        check::Check,
+        client::{ConnectionReuse, HttpVersion},
+        cookie::CookieMode,
+        descriptors::ProtoSources,
+        extract::Extractor,
+        grpc_requester::{self, GrpcCompression},
+        params::{GrpcParams, HttpParams, RequestParams, WebsocketParams},
+        tls::TlsOptions,
+    },
+    scheduler::{
+        scheduler::Executor,
+        stages::{Stage, Stages},
+    },
+    template::feeder::FeedStrategy,
+};
+
+/// Load test described in a YAML or TOML file: the load settings and the
+/// mix of requests the workers send.
+pub struct Scenario {
+    pub concurrency: Option<u64>,
+    pub duration: Option<u64>,
+    pub rate: Option<u64>,
+    pub timeout: Option<u64>,
+    pub executor: Option<Executor>,
+    pub max_in_flight: Option<u64>,
+    pub stages: Option<Stages>,
+    pub data_file: Option<PathBuf>,
+    pub data_strategy: Option<FeedStrategy>,
+    pub thresholds: Vec<Threshold>,
+    pub flow: Flow,
+    pub cookie_jar: Option<CookieMode>,
+    pub cookies: BTreeMap<String, String>,
+    /// TLS settings with paths resolved against the directory of the file
+    pub tls: Option<TlsOptions>,
+    pub http_version: Option<HttpVersion>,
+    pub clients: Option<u64>,
+    pub max_concurrent_streams: Option<u32>,
+    pub connection_reuse: Option<ConnectionReuse>,
+    pub pool_size: Option<usize>,
+    pub idle_timeout: Option<Duration>,
+    pub max_redirects: Option<usize>,
+    pub templates: Option<bool>,
+    pub requests: Vec<ScenarioRequest>,
+}
+
+/// How an iteration goes through the requests of the scenario.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flow {
+    /// One request picked by weight
+    #[default]
+    Weighted,
+    /// Every request in order, as the steps of a user journey. The journey
+    /// stops at the first failed step
+    Sequence,
+}
+
+/// A request of the traffic mix. Every iteration picks one of the requests
+/// with a probability proportional to its weight.
+#[derive(Debug, Clone)]
+pub struct ScenarioRequest {
+    pub name: String,
+    pub weight: u32,
+    /// Tags to group the results by in addition to the built-in ones
+    pub tags: Tags,
+    pub params: RequestParams,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    concurrency: Option<u64>,
+    duration: Option<DurationValue>,
+    rate: Option<u64>,
+    timeout: Option<DurationValue>,
+    executor: Option<Executor>,
+    max_in_flight: Option<u64>,
+    stages: Option<Vec<StageFile>>,
+    data_file: Option<PathBuf>,
+    data_strategy: Option<FeedStrategy>,
+    #[serde(default)]
+    thresholds: Vec<String>,
+    #[serde(default)]
+    flow: Flow,
+    cookie_jar: Option<CookieMode>,
+    #[serde(default)]
+    cookies: BTreeMap<String, String>,
+    tls: Option<TlsOptions>,
+    http_version: Option<HttpVersion>,
+    clients: Option<u64>,
+    max_concurrent_streams: Option<u32>,
+    connection_reuse: Option<ConnectionReuse>,
+    pool_size: Option<usize>,
+    idle_timeout: Option<DurationValue>,
+    max_redirects: Option<usize>,
+    templates: Option<bool>,
+    requests: Vec<RequestFile>,
+}
+
+/// A single value or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    fn into_vec(self) -> Vec<T> {
+        match self {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// Either a number of seconds or a duration such as `90s` or `2m`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationValue {
+    Seconds(u64),
+    Text(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StageFile {
+    duration: DurationValue,
+    target: u64,
+}
+
+#[derive(Deserialize)]
+struct RequestFile {
+    name: Option<String>,
+    #[serde(default = "default_weight")]
+    weight: u32,
+    #[serde(default)]
+    tags: Tags,
+    #[serde(flatten)]
+    params: ParamsFile,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_messages() -> u64 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "protocol", rename_all = "lowercase", deny_unknown_fields)]
+enum ParamsFile {
+    Http {
+        url: String,
+        method: Option<String>,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        body: Option<serde_json::Value>,
+        #[serde(default)]
+        form: HashMap<String, String>,
+        #[serde(default)]
+        checks: Vec<String>,
+        #[serde(default)]
+        extract: BTreeMap<String, String>,
+    },
+    Grpc {
+        address: String,
+        proto: Option<OneOrMany<String>>,
+        #[serde(default)]
+        import_paths: Vec<String>,
+        method: String,
+        data: Option<serde_json::Value>,
+        #[serde(default = "default_messages")]
+        messages: u64,
+        #[serde(default)]
+        metadata: BTreeMap<String, String>,
+        deadline: Option<DurationValue>,
+        compression: Option<GrpcCompression>,
+        #[serde(default)]
+        checks: Vec<String>,
+        /// Only read to reject it, values are extracted from HTTP responses
+        #[serde(default)]
+        extract: BTreeMap<String, String>,
+    },
+    Websocket {
+        url: String,
+        data: serde_json::Value,
+        #[serde(default)]
+        extract: BTreeMap<String, String>,
+    },
+}
+
+impl Scenario {
+    /// Reads a scenario from a `.yaml`, `.yml` or `.toml` file. Relative proto,
+    /// data file and certificate paths are resolved against the directory of
+    /// the scenario file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read scenario '{}'", path.display()))?;
+
+        let file: ScenarioFile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => serde_norway::from_str(&content)
+                .with_context(|| format!("invalid scenario '{}'", path.display()))?,
+            Some("toml") => toml::from_str(&content)
+                .with_context(|| format!("invalid scenario '{}'", path.display()))?,
+            _ => {
+                return Err(anyhow!(
+                    "unsupported scenario format '{}': expected .yaml, .yml or .toml",
+                    path.display()
+                ))
+            }
+        };
+
+        if file.requests.is_empty() {
+            return Err(anyhow!("scenario '{}' has no requests", path.display()));
+        }
+
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+
+        let requests: Vec<ScenarioRequest> = file
+            .requests
+            .into_iter()
+            .map(|request| request.into_request(base_dir))
+            .collect::<Result<_>>()?;
+
+        // The results are reported by name, so two requests with the same
+        // name would be merged into one
+        let mut names = HashSet::new();
+        if let Some(duplicate) = requests.iter().find(|request| !names.insert(&request.name)) {
+            return Err(anyhow!(
+                "scenario '{}' has more than one request named '{}', give them distinct names",
+                path.display(),
+                duplicate.name
+            ));
+        }
+
+        Ok(Scenario {
+            concurrency: file.concurrency,
+            duration: file.duration.map(DurationValue::seconds).transpose()?,
+            rate: file.rate,
+            timeout: file.timeout.map(DurationValue::seconds).transpose()?,
+            executor: file.executor,
+            max_in_flight: file.max_in_flight,
+            stages: file
+                .stages
+                .map(|stages| {
+                    stages
+                        .into_iter()
+                        .map(|stage| {
+                            let duration = stage.duration.duration()?;
+                            if duration.is_zero() {
+                                return Err(anyhow!("stage duration must be positive"));
+                            }
+                            Ok(Stage {
+                                duration,
+                                target: stage.target,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()
+                        .map(Stages::new)
+                })
+                .transpose()?,
+            data_file: file.data_file.map(|data_file| base_dir.join(data_file)),
+            data_strategy: file.data_strategy,
+            thresholds: file
+                .thresholds
+                .iter()
+                .map(|threshold| Threshold::from_str(threshold).map_err(|e| anyhow!(e)))
+                .collect::<Result<_>>()?,
+            flow: file.flow,
+            cookie_jar: file.cookie_jar,
+            cookies: file.cookies,
+            tls: file.tls.map(|tls| {
+                let mut resolved = TlsOptions::default();
+                resolved.merge(&tls, base_dir);
+                resolved
+            }),
+            http_version: file.http_version,
+            clients: match file.clients {
+                Some(0) => return Err(anyhow!("clients must be positive")),
+                clients => clients,
+            },
+            max_concurrent_streams: match file.max_concurrent_streams {
+                Some(0) => return Err(anyhow!("max_concurrent_streams must be positive")),
+                max_concurrent_streams => max_concurrent_streams,
+            },
+            connection_reuse: file.connection_reuse,
+            pool_size: file.pool_size,
+            idle_timeout: file.idle_timeout.map(DurationValue::duration).transpose()?,
+            max_redirects: file.max_redirects,
+            templates: file.templates,
+            requests,
+        })
+    }
+
+    /// Fills the command line options that were not passed with the settings
+    /// of the scenario, options on the command line take precedence.
+    pub fn apply(&self, cli: &mut Cli, matches: &ArgMatches) {
+        let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+        if let Some(concurrency) = self.concurrency.filter(|_| unset("concurrency")) {
+            cli.concurrency = concurrency;
+        }
+        if let Some(duration) = self.duration.filter(|_| unset("duration")) {
+            cli.duration = duration;
+        }
+        if let Some(rate) = self.rate.filter(|_| unset("rate")) {
+            cli.rate = Some(rate);
+        }
+        if let Some(timeout) = self.timeout.filter(|_| unset("timeout")) {
+            cli.timeout = timeout;
+        }
+        if let Some(executor) = self.executor.filter(|_| unset("executor")) {
+            cli.executor = executor;
+        }
+        if let Some(max_in_flight) = self.max_in_flight.filter(|_| unset("max_in_flight")) {
+            cli.max_in_flight = Some(max_in_flight);
+        }
+        if let Some(stages) = self.stages.as_ref().filter(|_| unset("stages")) {
+            cli.stages = Some(stages.clone());
+        }
+        if let Some(data_file) = self.data_file.as_ref().filter(|_| unset("data_file")) {
+            cli.data_file = Some(data_file.clone());
+        }
+        if let Some(data_strategy) = self.data_strategy.filter(|_| unset("data_strategy")) {
+            cli.data_strategy = data_strategy;
+        }
+        if let Some(cookie_jar) = self.cookie_jar.filter(|_| unset("cookie_jar")) {
+            cli.cookie_jar = cookie_jar;
+        }
+        for (name, value) in &self.cookies {
+            if !cli.cookies.iter().any(|(cli_name, _)| cli_name == name) {
+                cli.cookies.push((name.clone(), value.clone()));
+            }
+        }
+        if let Some(tls) = &self.tls {
+            // Only the TLS options given on the command line are set
+            let mut merged = tls.clone();
+            merged.merge(&cli.tls, Path::new(""));
+            cli.tls = merged;
+        }
+        if let Some(http_version) = self.http_version.filter(|_| unset("http_version")) {
+            cli.connection.http_version = http_version;
+        }
+        if let Some(clients) = self.clients.filter(|_| unset("clients")) {
+            cli.connection.clients = Some(clients);
+        }
+        if let Some(max_concurrent_streams) = self
+            .max_concurrent_streams
+            .filter(|_| unset("max_concurrent_streams"))
+        {
+            cli.connection.max_concurrent_streams = Some(max_concurrent_streams);
+        }
+        if let Some(connection_reuse) = self.connection_reuse.filter(|_| unset("connection_reuse"))
+        {
+            cli.connection.connection_reuse = connection_reuse;
+        }
+        if let Some(pool_size) = self.pool_size.filter(|_| unset("pool_size")) {
+            cli.connection.pool_size = Some(pool_size);
+        }
+        if let Some(idle_timeout) = self.idle_timeout.filter(|_| unset("idle_timeout")) {
+            cli.connection.idle_timeout = Some(idle_timeout);
+        }
+        if let Some(max_redirects) = self.max_redirects.filter(|_| unset("max_redirects")) {
+            cli.connection.max_redirects = Some(max_redirects);
+        }
+        if let Some(templates) = self.templates.filter(|_| unset("templates")) {
+            cli.templates = templates;
+        }
+        cli.threshold.extend(self.thresholds.iter().cloned());
+    }
+}
+
+impl DurationValue {
+    fn duration(self) -> Result<Duration> {
+        match self {
+            DurationValue::Seconds(seconds) => Ok(Duration::from_secs(seconds)),
+            DurationValue::Text(text) => humantime::parse_duration(&text)
+                .with_context(|| format!("invalid duration '{}'", text)),
+        }
+    }
+
+    fn seconds(self) -> Result<u64> {
+        Ok(self.duration()?.as_secs_f64().ceil() as u64)
+    }
+}
+
+impl ScenarioRequest {
+    /// Creates a request with weight 1, named after its method and URL.
+    pub fn new(params: RequestParams) -> Self {
+        let name = match &params {
+            RequestParams::Http(params) => format!("{} {}", params.method, params.url),
+            RequestParams::Grpc(params) => params.method.clone(),
+            RequestParams::Websocket(params) => params.url.clone(),
+        };
+
+        ScenarioRequest {
+            name,
+            weight: 1,
+            tags: Tags::new(),
+            params,
+        }
+    }
+
+    /// Tags of the request: its name, protocol, method and URL, which is the
+    /// template the request is rendered from, and the tags of the scenario.
+    pub fn tags(&self) -> Tags {
+        let mut tags = Tags::new();
+        tags.insert("name".to_string(), self.name.clone());
+
+        match &self.params {
+            RequestParams::Http(params) => {
+                tags.insert("protocol".to_string(), "http".to_string());
+                tags.insert("method".to_string(), params.method.to_string());
+                tags.insert("url".to_string(), params.url.clone());
+            }
+            RequestParams::Grpc(params) => {
+                tags.insert("protocol".to_string(), "grpc".to_string());
+                tags.insert("method".to_string(), params.method.clone());
+                tags.insert("url".to_string(), params.address.clone());
+            }
+            RequestParams::Websocket(params) => {
+                tags.insert("protocol".to_string(), "websocket".to_string());
+                tags.insert("url".to_string(), params.url.clone());
+            }
+        }
+
+        tags.extend(self.tags.clone());
+        tags
+    }
+}
+
+impl RequestFile {
+    fn into_request(self, base_dir: &Path) -> Result<ScenarioRequest> {
+        if self.weight == 0 {
+            return Err(anyhow!("request weight must be positive"));
+        }
+
+        let request = ScenarioRequest::new(self.params.into_params(base_dir)?);
+
+        Ok(ScenarioRequest {
+            name: self.name.unwrap_or(request.name),
+            weight: self.weight,
+            tags: self.tags,
+            params: request.params,
+        })
+    }
+}
+
+impl ParamsFile {
+    fn into_params(self, base_dir: &Path) -> Result<RequestParams> {
+        match self {
+            ParamsFile::Http {
+                url,
+                method,
+                headers,
+                body,
+                form,
+                checks,
+                extract,
+            } => {
+                let method = match method {
+                    Some(method) => Method::from_str(&method.to_uppercase())
+                        .with_context(|| format!("invalid HTTP method '{}'", method))?,
+                    None => Method::GET,
+                };
+
+                let mut header_map = HeaderMap::new();
+                for (name, value) in &headers {
+                    header_map.insert(
+                        HeaderName::from_bytes(name.as_bytes())
+                            .with_context(|| format!("invalid header name '{}'", name))?,
+                        HeaderValue::from_str(value)
+                            .with_context(|| format!("invalid value of header '{}'", name))?,
+                    );
+                }
+
+                Ok(RequestParams::Http(HttpParams {
+                    url,
+                    method,
+                    headers: header_map,
+                    body: body.map(payload),
+                    form,
+                    checks: checks
+                        .iter()
+                        .map(|check| Check::parse_http(check).map_err(|e| anyhow!(e)))
+                        .collect::<Result<_>>()?,
+                    extract: extract
+                        .into_iter()
+                        .map(|(name, extractor)| {
+                            Ok((
+                                name,
+                                Extractor::from_str(&extractor).map_err(|e| anyhow!(e))?,
+                            ))
+                        })
+                        .collect::<Result<_>>()?,
+                }))
+            }
+            ParamsFile::Grpc {
+                address,
+                proto,
+                import_paths,
+                method,
+                data,
+                messages,
+                metadata,
+                deadline,
+                compression,
+                checks,
+                extract,
+            } => {
+                if !extract.is_empty() {
+                    return Err(anyhow!("extract is only supported for HTTP requests"));
+                }
+                if messages == 0 {
+                    return Err(anyhow!("messages must be positive"));
+                }
+
+                let mut metadata_map = tonic::metadata::MetadataMap::new();
+                for (key, value) in &metadata {
+                    grpc_requester::insert_metadata(&mut metadata_map, key, value)
+                        .map_err(|e| anyhow!(e))?;
+                }
+
+                Ok(RequestParams::Grpc(GrpcParams {
+                    address,
+                    protos: ProtoSources {
+                        files: proto
+                            .map(OneOrMany::into_vec)
+                            .unwrap_or_default()
+                            .iter()
+                            .map(|proto| relative_to(base_dir, proto))
+                            .collect(),
+                        import_paths: import_paths
+                            .iter()
+                            .map(|path| relative_to(base_dir, path))
+                            .collect(),
+                    },
+                    method,
+                    data: data.map(payload),
+                    messages,
+                    metadata: metadata_map,
+                    deadline: deadline.map(DurationValue::duration).transpose()?,
+                    compression,
+                    checks: checks
+                        .iter()
+                        .map(|check| Check::parse_grpc(check).map_err(|e| anyhow!(e)))
+                        .collect::<Result<_>>()?,
+                }))
+            }
+            ParamsFile::Websocket { url, data, extract } => {
+                if !extract.is_empty() {
+                    return Err(anyhow!("extract is only supported for HTTP requests"));
+                }
+
+                Ok(RequestParams::Websocket(WebsocketParams {
+                    url,
+                    data: payload(data),
+                }))
+            }
+        }
+    }
+}
+
+fn relative_to(base_dir: &Path, path: &str) -> String {
+    base_dir.join(path).to_string_lossy().into_owned()
+}
+
+/// Strings are sent as they are, structured values are sent as JSON so that
+/// bodies can be written inline in the scenario.
+fn payload(value: serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text,
+        value => value.to_string(),
+    }
+}
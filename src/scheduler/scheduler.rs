@@ -1,53 +1,201 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use indicatif::{ProgressBar, ProgressStyle};
+use tokio::{
+    sync::{mpsc, Barrier, Mutex, OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
 
 use crate::{
-    metrics::metrics::Metrics,
+    metrics::{
+        metrics::Metrics,
+        tagged::{self, TaggedMetrics},
+    },
+    report::summary::Summary,
     requester::{
-        error::RequestError, grpc_requester::GrpcRequester, http_requester::HttpRequester,
-        params::RequestParams, websocket_requester::WebsocketRequester, Requester,
+        client::{ClientOptions, ConnectionOptions, RequestClients},
+        cookie::CookieJar,
+        error::RequestError,
+        proxy::ProxyConfig,
+        scenario_requester::ScenarioRequester,
+        tls::TlsConfig,
+        Requester,
     },
+    scenario::scenario::{Flow, ScenarioRequest},
+    scheduler::stages::Stages,
+    template::{context::RequestContext, feeder::Feeder, template::Template},
 };
 
-pub struct Scheduler<'a> {
-    metrics: &'a Arc<Metrics>,
+/// Load model used to issue requests.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Executor {
+    /// Each worker sends its next request only after the previous one completed
+    Closed,
+    /// A central ticker issues requests at a constant arrival rate
+    Open,
+}
+
+/// How often the load profile is re-evaluated while following stages.
+const STAGE_RESOLUTION: Duration = Duration::from_millis(100);
+
+#[derive(Clone)]
+pub struct SchedulerConfig {
+    pub concurrency: u64,
+    pub duration: u64,
+    pub rate: Option<u64>,
+    pub timeout: u64,
+    pub show_progress: bool,
+    pub executor: Executor,
+    pub max_in_flight: u64,
+    pub stages: Option<Stages>,
+    pub snapshot_interval: u64,
+    pub show_timeline: bool,
+    pub group_by: String,
+    pub feeder: Option<Arc<Feeder>>,
+    pub flow: Flow,
+    /// Cookies every worker starts with
+    pub cookies: CookieJar,
+    pub tls: Arc<TlsConfig>,
+    pub proxy: Arc<ProxyConfig>,
+    pub connection: ConnectionOptions,
+    /// Render requests as templates even without a data file or extracted
+    /// values, which can only be used through templates
+    pub templates: bool,
+}
+
+/// An iteration issued by the open model ticker. The permit is held until the
+/// iteration completes and bounds the number of in-flight requests.
+struct Tick {
+    intended: Instant,
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Everything a closed model worker needs, so that workers can be spawned
+/// while the test is running.
+struct ClosedWorker {
+    metrics: Arc<Metrics>,
+    requests: Vec<ScenarioRequest>,
+    tagged: Vec<TaggedMetrics>,
+    clients: Vec<RequestClients>,
+    start_bench: Instant,
     concurrency: u64,
     duration: u64,
     rate: Option<u64>,
-    timeout: u64,
-    show_progress: bool,
-    request_params: RequestParams,
+    feeder: Option<Arc<Feeder>>,
+    flow: Flow,
+    cookies: CookieJar,
+    options: ClientOptions,
+}
+
+impl ClosedWorker {
+    fn is_exhausted(&self) -> bool {
+        self.feeder
+            .as_ref()
+            .is_some_and(|feeder| feeder.is_exhausted())
+    }
+
+    fn spawn(&self, worker_id: u64, stop: Arc<AtomicBool>) -> JoinHandle<()> {
+        let metrics = Arc::clone(&self.metrics);
+        let requests = self.requests.clone();
+        let tagged = self.tagged.clone();
+        let clients = self.clients.clone();
+        let start_bench = self.start_bench;
+        let duration = self.duration;
+        let feeder = self.feeder.clone();
+        let flow = self.flow;
+        let cookies = self.cookies.clone();
+        let options = self.options.clone();
+        let interval = self.rate.map(|rps| {
+            let per_worker = (rps as f64) / (self.concurrency as f64);
+            Duration::from_secs_f64(1.0 / per_worker)
+        });
+
+        tokio::spawn(async move {
+            let requester =
+                ScenarioRequester::new(&requests, &tagged, &clients, flow, &options, worker_id);
+
+            // Errors were reported before the test started, a worker that
+            // still fails records them with every request
+            let _ = requester.initialize().await;
+
+            Scheduler::run_client(
+                &metrics,
+                start_bench,
+                requester,
+                RequestContext::new(worker_id, feeder).with_cookies(cookies),
+                duration,
+                interval,
+                &stop,
+            )
+            .await;
+        })
+    }
+}
+
+pub struct Scheduler<'a> {
+    metrics: &'a Arc<Metrics>,
+    config: SchedulerConfig,
+    requests: Vec<ScenarioRequest>,
+    tagged: Vec<TaggedMetrics>,
+    clients: Vec<RequestClients>,
 }
 
 impl<'a> Scheduler<'a> {
     pub fn new(
         metrics: &'a Arc<Metrics>,
-        concurrency: u64,
-        duration: u64,
-        rate: Option<u64>,
-        timeout: u64,
-        show_progress: bool,
-        request_params: RequestParams,
+        config: SchedulerConfig,
+        requests: Vec<ScenarioRequest>,
     ) -> Self {
+        let tagged = requests
+            .iter()
+            .map(|request| TaggedMetrics::new(request.tags(), metrics))
+            .collect();
+        let clients = requests
+            .iter()
+            .map(|_| RequestClients::new(&config.connection))
+            .collect();
+
         Scheduler {
             metrics,
-            concurrency,
-            duration,
-            rate,
-            timeout,
-            show_progress,
-            request_params,
+            config,
+            requests,
+            tagged,
+            clients,
         }
     }
 
-    pub async fn run(&self) {
-        let start_bench = std::time::Instant::now();
+    /// Runs the test and returns how long it ran, measured once when the last
+    /// worker stopped. Fails before the test starts when the requests cannot
+    /// be initialised.
+    pub async fn run(&self) -> Result<Duration, RequestError> {
+        self.check_initialize().await?;
+
         let mut tasks = Vec::new();
 
-        let duration = self.duration;
+        let open_queue = match self.config.executor {
+            Executor::Closed => None,
+            Executor::Open => Some(self.spawn_open_workers(&mut tasks).await),
+        };
+
+        let start_bench = std::time::Instant::now();
+
+        let duration = self.config.duration;
 
-        if self.show_progress {
+        // Progress and snapshots only follow the workers, which can stop
+        // before the end of the test when the data file is exhausted
+        let mut reporters = Vec::new();
+
+        let bar = if self.config.show_progress {
             let bar = ProgressBar::new(duration);
             let bar = bar.with_message("Hammering");
             bar.set_style(
@@ -56,8 +204,9 @@ impl<'a> Scheduler<'a> {
                     .progress_chars("##-"),
             );
 
-            tasks.push(tokio::spawn({
+            reporters.push(tokio::spawn({
                 let bar = bar.clone();
+                let metrics = Arc::clone(self.metrics);
                 async move {
                     let mut seconds_left = duration;
                     let mut interval = tokio::time::interval(Duration::from_secs(1));
@@ -66,145 +215,471 @@ impl<'a> Scheduler<'a> {
                         interval.tick().await;
                         bar.inc(1);
                         seconds_left -= 1;
+
+                        if let Some(snapshot) = metrics.last_snapshot().await {
+                            bar.set_message(format!(
+                                "Hammering {:.0}/s P(99) {}",
+                                snapshot.rps(),
+                                Metrics::format_micros(snapshot.latency.p99)
+                            ));
+                        }
                     }
-                    bar.finish_and_clear();
                 }
             }));
+
+            Some(bar)
+        } else {
+            None
+        };
+
+        self.spawn_snapshot_collector(&mut reporters, start_bench);
+
+        match open_queue {
+            None => self.spawn_closed_workers(&mut tasks, start_bench),
+            Some((sender, in_flight)) => {
+                self.spawn_ticker(&mut tasks, start_bench, sender, in_flight)
+            }
         }
 
-        for _ in 0..self.concurrency {
-            let concurrency = self.concurrency;
-            let duration = self.duration;
-            let rate = self.rate;
-            let timeout = self.timeout;
-            let metrics = Arc::clone(self.metrics);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        for reporter in reporters {
+            reporter.abort();
+        }
+        if let Some(bar) = bar {
+            bar.finish_and_clear();
+        }
 
-            // Clone the command for each task to avoid moving out of self
-            let request_params = self.request_params.clone();
+        let elapsed = start_bench.elapsed();
 
-            tasks.push(tokio::spawn(async move {
-                match request_params {
-                    RequestParams::Http(params) => {
-                        let requester = HttpRequester::new(
-                            &metrics,
-                            params.method,
-                            params.url,
-                            params.body,
-                            params.form,
-                            params.headers,
-                            timeout,
-                        );
-
-                        let _ = requester.initialize().await;
-
-                        Scheduler::run_client(
-                            &metrics,
-                            start_bench,
-                            requester,
-                            concurrency,
-                            duration,
-                            rate,
-                        )
-                        .await;
-                    }
-                    RequestParams::Grpc(params) => {
-                        let requester = GrpcRequester::new(
-                            &metrics,
-                            params.address,
-                            params.proto,
-                            params.method,
-                            params.data,
-                            timeout,
-                        );
-
-                        let _ = requester.initialize().await;
-
-                        Scheduler::run_client(
-                            &metrics,
-                            start_bench,
-                            requester,
-                            concurrency,
-                            duration,
-                            rate,
-                        )
-                        .await;
-                    }
-                    RequestParams::Websocket(params) => {
-                        let requester = WebsocketRequester::new(&metrics, params.url, params.data);
-
-                        let _ = requester.initialize().await;
-
-                        Scheduler::run_client(
-                            &metrics,
-                            start_bench,
-                            requester,
-                            concurrency,
-                            duration,
-                            rate,
-                        )
-                        .await;
-                    }
+        // The last interval also covers requests that were still in flight
+        self.metrics.take_snapshot(elapsed).await;
+
+        Ok(elapsed)
+    }
+
+    /// Initialises the requests once up front so that invalid templates,
+    /// URLs, TLS settings, protos or an unreachable target stop the run
+    /// instead of failing every request. Recorded into scratch metrics so the
+    /// report only covers the test itself.
+    async fn check_initialize(&self) -> Result<(), RequestError> {
+        self.check_variables()?;
+
+        let scratch = Arc::new(Metrics::new());
+        let tagged: Vec<TaggedMetrics> = self
+            .requests
+            .iter()
+            .map(|request| TaggedMetrics::new(request.tags(), &scratch))
+            .collect();
+        let clients: Vec<RequestClients> =
+            self.clients.iter().map(RequestClients::unshared).collect();
+        let requester = ScenarioRequester::new(
+            &self.requests,
+            &tagged,
+            &clients,
+            self.config.flow,
+            &self.client_options(),
+            0,
+        );
+
+        requester.initialize().await
+    }
+
+    fn client_options(&self) -> ClientOptions {
+        ClientOptions {
+            timeout: self.config.timeout,
+            tls: Arc::clone(&self.config.tls),
+            proxy: Arc::clone(&self.config.proxy),
+            connection: self.config.connection.clone(),
+            templates: self.templates(),
+        }
+    }
+
+    fn templates(&self) -> bool {
+        self.config.templates
+            || self.config.feeder.is_some()
+            || self
+                .requests
+                .iter()
+                .any(|request| !request.params.extracted().is_empty())
+    }
+
+    /// Checks that templates only use the columns of the data file and the
+    /// values extracted from responses, so that a misspelled name does not
+    /// fail every request.
+    fn check_variables(&self) -> Result<(), RequestError> {
+        if !self.templates() {
+            return Ok(());
+        }
+
+        let mut known: HashSet<&str> = self
+            .config
+            .feeder
+            .as_ref()
+            .map(|feeder| feeder.columns())
+            .unwrap_or_default();
+        known.extend(
+            self.requests
+                .iter()
+                .flat_map(|request| request.params.extracted()),
+        );
+
+        for request in &self.requests {
+            for source in request.params.templates() {
+                // Templates that do not parse are reported by the requester
+                let Ok(template) = source.parse::<Template>() else {
+                    continue;
                 };
+                let unknown = template.variables().find(|name| !known.contains(name));
+                if let Some(name) = unknown {
+                    return Err(RequestError::ConfigError(format!(
+                        "Invalid template '{}': unknown variable '{}', it is neither a column of the data file nor extracted from a response",
+                        source, name
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closes a metrics interval every `snapshot_interval` seconds.
+    fn spawn_snapshot_collector(&self, tasks: &mut Vec<JoinHandle<()>>, start_bench: Instant) {
+        let metrics = Arc::clone(self.metrics);
+        let every = Duration::from_secs(self.config.snapshot_interval.max(1));
+        let duration = Duration::from_secs(self.config.duration);
+
+        tasks.push(tokio::spawn(async move {
+            let start = tokio::time::Instant::from_std(start_bench);
+            let mut next = start + every;
+
+            while next < start + duration {
+                tokio::time::sleep_until(next).await;
+                metrics.take_snapshot(next - start).await;
+                next += every;
+            }
+        }));
+    }
+
+    fn spawn_closed_workers(&self, tasks: &mut Vec<JoinHandle<()>>, start_bench: Instant) {
+        let worker = ClosedWorker {
+            metrics: Arc::clone(self.metrics),
+            requests: self.requests.clone(),
+            tagged: self.tagged.clone(),
+            clients: self.clients.clone(),
+            start_bench,
+            concurrency: self.config.concurrency,
+            duration: self.config.duration,
+            rate: self.config.rate,
+            feeder: self.config.feeder.clone(),
+            flow: self.config.flow,
+            cookies: self.config.cookies.clone(),
+            options: self.client_options(),
+        };
+
+        match self.config.stages.clone() {
+            Some(stages) => tasks.push(tokio::spawn(async move {
+                Scheduler::run_stages(worker, stages).await;
+            })),
+            None => {
+                for worker_id in 0..self.config.concurrency {
+                    tasks.push(worker.spawn(worker_id, Arc::new(AtomicBool::new(false))));
+                }
+            }
+        }
+    }
+
+    /// Spawns the open model workers and waits until all of them are
+    /// initialised, so that connection setup does not eat into the schedule.
+    async fn spawn_open_workers(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+    ) -> (mpsc::UnboundedSender<Tick>, Arc<Semaphore>) {
+        let (sender, receiver) = mpsc::unbounded_channel::<Tick>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let in_flight = Arc::new(Semaphore::new(self.config.max_in_flight as usize));
+        let ready = Arc::new(Barrier::new(self.config.max_in_flight as usize + 1));
+
+        for worker_id in 0..self.config.max_in_flight {
+            let metrics = Arc::clone(self.metrics);
+            let receiver = Arc::clone(&receiver);
+            let ready = Arc::clone(&ready);
+            let requests = self.requests.clone();
+            let tagged = self.tagged.clone();
+            let clients = self.clients.clone();
+            let feeder = self.config.feeder.clone();
+            let flow = self.config.flow;
+            let cookies = self.config.cookies.clone();
+            let options = self.client_options();
+
+            tasks.push(tokio::spawn(async move {
+                let requester =
+                    ScenarioRequester::new(&requests, &tagged, &clients, flow, &options, worker_id);
+
+                // Errors were reported before the test started, a worker that
+                // still fails records them with every request
+                let _ = requester.initialize().await;
+                ready.wait().await;
+
+                Scheduler::run_open_client(
+                    &metrics,
+                    requester,
+                    RequestContext::new(worker_id, feeder).with_cookies(cookies),
+                    receiver,
+                )
+                .await;
             }));
         }
 
-        for task in tasks {
-            task.await.unwrap();
+        ready.wait().await;
+
+        (sender, in_flight)
+    }
+
+    fn spawn_ticker(
+        &self,
+        tasks: &mut Vec<JoinHandle<()>>,
+        start_bench: Instant,
+        sender: mpsc::UnboundedSender<Tick>,
+        in_flight: Arc<Semaphore>,
+    ) {
+        let rate = self.config.rate.unwrap_or(0);
+        let stages = self.config.stages.clone();
+        let duration = Duration::from_secs(self.config.duration);
+        let metrics = Arc::clone(self.metrics);
+
+        tasks.push(tokio::spawn(async move {
+            let target_rate = |elapsed: Duration| match &stages {
+                Some(stages) => stages.target_at(elapsed),
+                None => rate as f64,
+            };
+
+            Scheduler::run_ticker(
+                &metrics,
+                start_bench,
+                sender,
+                in_flight,
+                target_rate,
+                duration,
+            )
+            .await;
+        }));
+    }
+
+    /// Spawns and retires closed model workers so that the number of active
+    /// workers follows the stages.
+    async fn run_stages(worker: ClosedWorker, stages: Stages) {
+        let mut active: Vec<(u64, Arc<AtomicBool>, JoinHandle<()>)> = Vec::new();
+        let mut retired: Vec<(u64, JoinHandle<()>)> = Vec::new();
+        let mut interval = tokio::time::interval(STAGE_RESOLUTION);
+
+        loop {
+            interval.tick().await;
+
+            let elapsed = worker.start_bench.elapsed();
+            if elapsed >= stages.duration() || worker.is_exhausted() {
+                break;
+            }
+
+            let (finished, running): (Vec<_>, Vec<_>) = retired
+                .into_iter()
+                .partition(|(_, handle)| handle.is_finished());
+            for (_, handle) in finished {
+                handle.await.unwrap();
+            }
+            retired = running;
+
+            let target = stages.target_at(elapsed).round() as usize;
+            while active.len() < target {
+                // Ids stay below the peak target, the number of workers the
+                // rows of the data file are split between. The id of a retired
+                // worker is only reused once its last iteration finished, a
+                // new worker waits for that otherwise
+                let in_use = |id: &u64| {
+                    active.iter().any(|(active_id, ..)| active_id == id)
+                        || retired.iter().any(|(retired_id, _)| retired_id == id)
+                };
+                let Some(worker_id) = (0..worker.concurrency).find(|id| !in_use(id)) else {
+                    break;
+                };
+
+                let stop = Arc::new(AtomicBool::new(false));
+                active.push((worker_id, Arc::clone(&stop), worker.spawn(worker_id, stop)));
+            }
+            while active.len() > target {
+                if let Some((worker_id, stop, handle)) = active.pop() {
+                    stop.store(true, Ordering::Relaxed);
+                    retired.push((worker_id, handle));
+                }
+            }
         }
 
-        self.print_report(start_bench).await;
+        for (worker_id, stop, handle) in active {
+            stop.store(true, Ordering::Relaxed);
+            retired.push((worker_id, handle));
+        }
+        for (_, handle) in retired {
+            handle.await.unwrap();
+        }
     }
 
     async fn run_client<R>(
         metrics: &Arc<Metrics>,
         start_bench: std::time::Instant,
         requester: R,
-        concurrency: u64,
+        mut context: RequestContext,
         duration: u64,
-        rate: Option<u64>,
+        interval: Option<Duration>,
+        stop: &AtomicBool,
     ) where
         R: Requester + Send,
     {
-        let interval = rate.map(|rps| {
-            let per_worker = (rps as f64) / (concurrency as f64);
-            Duration::from_secs_f64(1.0 / per_worker)
-        });
+        // Iterations are scheduled from the first one of the worker, workers
+        // added by a stage start their schedule when they are spawned
+        let first = std::time::Instant::now();
+        let mut scheduled: u32 = 0;
 
         loop {
-            let loop_start = std::time::Instant::now();
+            let intended = interval.map(|interval| first + interval * scheduled);
+            scheduled += 1;
+
+            if !context.next_iteration() {
+                break;
+            }
 
-            let result = requester.request().await;
+            // The requester records the outcome in the metrics of the request
+            let _ = requester.request(&mut context).await;
 
-            Self::handle_request_result(metrics, result).await;
+            // A worker that stalls sends the iterations it fell behind on
+            // right away, measuring from when each of them was due keeps the
+            // time they waited in the latency
+            if let Some(intended) = intended {
+                metrics
+                    .record_corrected_latency(Self::micros_since(intended), 0)
+                    .await;
+            }
 
-            if std::time::Instant::now() >= start_bench + std::time::Duration::from_secs(duration) {
+            if std::time::Instant::now() >= start_bench + std::time::Duration::from_secs(duration)
+                || stop.load(Ordering::Relaxed)
+            {
                 break;
             }
 
             // Enforce rate limiting
             if let Some(interval) = interval {
-                let elapsed = loop_start.elapsed();
-                if elapsed < interval {
-                    tokio::time::sleep(interval - elapsed).await;
-                }
+                tokio::time::sleep_until((first + interval * scheduled).into()).await;
             }
         }
     }
 
-    async fn handle_request_result(metrics: &Arc<Metrics>, result: Result<(), RequestError>) {
-        metrics.increment_total_requests().await;
-        match result {
-            Ok(_body) => {
-                metrics.increment_successful_requests().await;
+    /// Issues iterations at the target arrival rate, independently of how many
+    /// requests are still in flight. When every in-flight slot is taken the
+    /// iteration is dropped and counted instead of being delayed.
+    async fn run_ticker(
+        metrics: &Arc<Metrics>,
+        start_bench: Instant,
+        sender: mpsc::UnboundedSender<Tick>,
+        in_flight: Arc<Semaphore>,
+        target_rate: impl Fn(Duration) -> f64,
+        duration: Duration,
+    ) {
+        let start = tokio::time::Instant::from_std(start_bench);
+        let end = start + duration;
+        let mut next = start;
+        let mut last = start;
+        // Fraction of an iteration accumulated since the last one was issued
+        let mut credit = if target_rate(Duration::ZERO) > 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+
+        while next < end {
+            tokio::time::sleep_until(next).await;
+
+            credit += target_rate(last - start) * (next - last).as_secs_f64();
+            last = next;
+
+            while credit >= 1.0 {
+                credit -= 1.0;
+
+                match Arc::clone(&in_flight).try_acquire_owned() {
+                    Ok(permit) => {
+                        let tick = Tick {
+                            intended: next.into_std(),
+                            _permit: permit,
+                        };
+                        if sender.send(tick).is_err() {
+                            return;
+                        }
+                    }
+                    Err(_) => metrics.increment_dropped_iterations().await,
+                }
             }
-            Err(err) => {
-                println!("Request failed {:?}", err);
-                metrics.increment_failed_requests().await;
+
+            // Wake up when the next iteration is due at the current rate, but
+            // often enough to follow a ramp
+            let rate = target_rate(next - start);
+            let until_due = if rate > 0.0 {
+                Duration::from_secs_f64((1.0 - credit) / rate)
+            } else {
+                STAGE_RESOLUTION
+            };
+            next += until_due.clamp(Duration::from_micros(1), STAGE_RESOLUTION);
+        }
+    }
+
+    async fn run_open_client<R>(
+        metrics: &Arc<Metrics>,
+        requester: R,
+        mut context: RequestContext,
+        receiver: Arc<Mutex<mpsc::UnboundedReceiver<Tick>>>,
+    ) where
+        R: Requester + Send,
+    {
+        loop {
+            let tick = receiver.lock().await.recv().await;
+            let Some(tick) = tick else {
+                break;
+            };
+
+            // Workers that run out of rows stop taking iterations, the ticker
+            // stops once all of them are gone
+            if !context.next_iteration() {
+                break;
             }
-        };
+
+            // The requester records the outcome in the metrics of the request
+            let _ = requester.request(&mut context).await;
+
+            // The ticker keeps issuing iterations while the target is slow, so
+            // measuring from the intended send time already includes queueing
+            metrics
+                .record_corrected_latency(Self::micros_since(tick.intended), 0)
+                .await;
+
+            drop(tick);
+        }
     }
 
-    async fn print_report(&self, start_bench: std::time::Instant) {
+    fn micros_since(intended: Instant) -> u64 {
+        Metrics::micros(intended.elapsed())
+    }
+
+    pub async fn summary(&self, elapsed: Duration) -> Summary {
+        Summary::collect(
+            self.metrics,
+            &self.config,
+            &self.requests,
+            &self.tagged,
+            elapsed,
+        )
+        .await
+    }
+
+    pub async fn print_report(&self, elapsed: Duration) {
         let total_requests = self.metrics.total_requests().await;
         let successful_requests = self.metrics.successful_requests().await;
         let failed_requests = self.metrics.failed_requests().await;
@@ -212,12 +687,18 @@ impl<'a> Scheduler<'a> {
         let success_rate = successful_requests as f64 / total_requests as f64 * 100.0;
         let fail_rate = failed_requests as f64 / total_requests as f64 * 100.0;
 
-        println!("");
+        println!();
         println!(
             "Requests:......................{:<10} {:>10.2}/s",
             total_requests,
-            self.metrics.rps(start_bench).await
+            self.metrics.rps(elapsed).await
         );
+        if self.config.executor == Executor::Open {
+            println!(
+                "Iterations dropped:............{}",
+                self.metrics.dropped_iterations().await
+            );
+        }
         println!(
             "Requests succeded:.............{:<10}  {:>10.2}%",
             successful_requests, success_rate
@@ -231,53 +712,270 @@ impl<'a> Scheduler<'a> {
             self.metrics
                 .human_readable_bytes(self.metrics.bytes_sent().await as f64),
             self.metrics
-                .human_readable_bytes(self.metrics.throughput_sent(start_bench).await)
+                .human_readable_bytes(self.metrics.throughput_sent(elapsed).await)
         );
         println!(
             "Data received:.................{:<10} {:>10}/s",
             self.metrics
                 .human_readable_bytes(self.metrics.bytes_received().await as f64),
             self.metrics
-                .human_readable_bytes(self.metrics.throughput_received(start_bench).await)
+                .human_readable_bytes(self.metrics.throughput_received(elapsed).await)
+        );
+        let compressed_sent = self.metrics.compressed_bytes_sent().await;
+        let compressed_received = self.metrics.compressed_bytes_received().await;
+        if compressed_sent > 0 || compressed_received > 0 {
+            // Share of the uncompressed size that went over the wire
+            let ratio = |compressed: u64, bytes: u64| {
+                if bytes > 0 {
+                    compressed as f64 / bytes as f64 * 100.0
+                } else {
+                    0.0
+                }
+            };
+            println!(
+                "Compressed sent:...............{:<10} {:>10.2}%",
+                self.metrics.human_readable_bytes(compressed_sent as f64),
+                ratio(
+                    compressed_sent,
+                    self.metrics.uncompressed_bytes_sent().await
+                )
+            );
+            println!(
+                "Compressed received:...........{:<10} {:>10.2}%",
+                self.metrics
+                    .human_readable_bytes(compressed_received as f64),
+                ratio(
+                    compressed_received,
+                    self.metrics.uncompressed_bytes_received().await
+                )
+            );
+        }
+        let messages_sent = self.metrics.messages_sent().await;
+        let messages_received = self.metrics.messages_received().await;
+        if messages_sent > 0 || messages_received > 0 {
+            let elapsed = elapsed.as_secs_f64();
+            println!(
+                "Messages sent:.................{:<10} {:>10.2}/s",
+                messages_sent,
+                messages_sent as f64 / elapsed
+            );
+            println!(
+                "Messages received:.............{:<10} {:>10.2}/s",
+                messages_received,
+                messages_received as f64 / elapsed
+            );
+        }
+        println!(
+            "Connections opened:............{}",
+            self.metrics.connections_opened().await
         );
         println!("Latencies:");
         println!(
             "   Min:........................{}",
-            self.metrics.format_micros(self.metrics.min_latency().await)
+            Metrics::format_micros(self.metrics.min_latency().await)
         );
         println!(
             "   P(50):......................{}",
-            self.metrics
-                .format_micros(self.metrics.histogram().await.value_at_quantile(0.50))
+            Metrics::format_micros(self.metrics.histogram().await.value_at_quantile(0.50))
         );
         println!(
             "   P(90):......................{}",
-            self.metrics
-                .format_micros(self.metrics.histogram().await.value_at_quantile(0.90))
+            Metrics::format_micros(self.metrics.histogram().await.value_at_quantile(0.90))
         );
         println!(
             "   P(95):......................{}",
-            self.metrics
-                .format_micros(self.metrics.histogram().await.value_at_quantile(0.95))
+            Metrics::format_micros(self.metrics.histogram().await.value_at_quantile(0.95))
         );
         println!(
             "   P(99):......................{}",
-            self.metrics
-                .format_micros(self.metrics.histogram().await.value_at_quantile(0.99))
+            Metrics::format_micros(self.metrics.histogram().await.value_at_quantile(0.99))
         );
         println!(
             "   P(99.9):....................{}",
-            self.metrics
-                .format_micros(self.metrics.histogram().await.value_at_quantile(0.999))
+            Metrics::format_micros(self.metrics.histogram().await.value_at_quantile(0.999))
         );
         println!(
             "   P(99.99):...................{}",
-            self.metrics
-                .format_micros(self.metrics.histogram().await.value_at_quantile(0.9999))
+            Metrics::format_micros(self.metrics.histogram().await.value_at_quantile(0.9999))
         );
         println!(
             "   Max:........................{}",
-            self.metrics.format_micros(self.metrics.max_latency().await)
+            Metrics::format_micros(self.metrics.max_latency().await)
+        );
+
+        if self.config.rate.is_some() || self.config.executor == Executor::Open {
+            let corrected = self.metrics.corrected_histogram().await;
+
+            println!("Latencies (corrected for coordinated omission):");
+            println!(
+                "   Min:........................{}",
+                Metrics::format_micros(corrected.min())
+            );
+            for (label, quantile) in [
+                ("   P(50):......................", 0.50),
+                ("   P(90):......................", 0.90),
+                ("   P(95):......................", 0.95),
+                ("   P(99):......................", 0.99),
+                ("   P(99.9):....................", 0.999),
+                ("   P(99.99):...................", 0.9999),
+            ] {
+                println!(
+                    "{}{}",
+                    label,
+                    Metrics::format_micros(corrected.value_at_quantile(quantile))
+                );
+            }
+            println!(
+                "   Max:........................{}",
+                Metrics::format_micros(corrected.max())
+            );
+        }
+
+        self.print_tag_groups(elapsed).await;
+        self.print_timings().await;
+        self.print_breakdown(total_requests).await;
+
+        if self.config.show_timeline {
+            self.print_timeline().await;
+        }
+    }
+
+    async fn print_tag_groups(&self, elapsed: Duration) {
+        let groups = tagged::group_by(&self.tagged, &self.config.group_by).await;
+        if groups.len() < 2 {
+            return;
+        }
+
+        let elapsed = elapsed.as_secs_f64();
+
+        println!(
+            "{:<32}{:>10} {:>12} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10}",
+            format!("Requests by {}:", self.config.group_by),
+            "Count",
+            "Rate",
+            "Failed",
+            "P(50)",
+            "P(99)",
+            "Max",
+            "Sent",
+            "Received"
+        );
+        for (value, group) in groups {
+            let label = format!("   {}:", value);
+            println!(
+                "{:.<32}{:>10} {:>10.2}/s {:>8} {:>8} {:>8} {:>8} {:>10} {:>10}",
+                label,
+                group.requests,
+                group.requests as f64 / elapsed,
+                group.failed,
+                Metrics::format_micros(group.hist.value_at_quantile(0.50)),
+                Metrics::format_micros(group.hist.value_at_quantile(0.99)),
+                Metrics::format_micros(group.hist.max()),
+                self.metrics.human_readable_bytes(group.bytes_sent as f64),
+                self.metrics
+                    .human_readable_bytes(group.bytes_received as f64),
+            );
+        }
+    }
+
+    async fn print_timings(&self) {
+        let timings = self.metrics.timings().await;
+        if timings.is_empty() {
+            return;
+        }
+
+        println!(
+            "{:<32}{:>8} {:>8} {:>8} {:>8} {:>10}",
+            "Timings:", "Avg", "P(50)", "P(99)", "Max", "Count"
+        );
+        for (timing, hist) in timings {
+            let label = format!("   {}:", timing.label());
+            println!(
+                "{:.<32}{:>8} {:>8} {:>8} {:>8} {:>10}",
+                label,
+                Metrics::format_micros(hist.mean() as u64),
+                Metrics::format_micros(hist.value_at_quantile(0.50)),
+                Metrics::format_micros(hist.value_at_quantile(0.99)),
+                Metrics::format_micros(hist.max()),
+                hist.len()
+            );
+        }
+    }
+
+    async fn print_breakdown(&self, total_requests: u64) {
+        let share = |count: u64| count as f64 / total_requests as f64 * 100.0;
+
+        let http_statuses = self.metrics.http_statuses().await;
+        if !http_statuses.is_empty() {
+            println!("Status codes:");
+            for (status, count) in http_statuses {
+                let label = format!("   {}:", status);
+                println!("{:.<32}{:<10}  {:>10.2}%", label, count, share(count));
+            }
+        }
+
+        let http_versions = self.metrics.http_versions().await;
+        if !http_versions.is_empty() {
+            println!("HTTP versions:");
+            for (version, count) in http_versions {
+                let label = format!("   {}:", version);
+                println!("{:.<32}{:<10}  {:>10.2}%", label, count, share(count));
+            }
+        }
+
+        let grpc_codes = self.metrics.grpc_codes().await;
+        if !grpc_codes.is_empty() {
+            println!("gRPC codes:");
+            for (code, count) in grpc_codes {
+                let label = format!("   {:?}:", code);
+                println!("{:.<32}{:<10}  {:>10.2}%", label, count, share(count));
+            }
+        }
+
+        let checks = self.metrics.checks().await;
+        if !checks.is_empty() {
+            println!("Checks:");
+            for (name, stats) in checks {
+                let label = format!("   {}:", name);
+                let total = stats.passed + stats.failed;
+                println!(
+                    "{:.<32}{:<10}  {:>10.2}%  {} failed",
+                    label,
+                    stats.passed,
+                    stats.passed as f64 / total as f64 * 100.0,
+                    stats.failed
+                );
+            }
+        }
+
+        let errors = self.metrics.errors().await;
+        if !errors.is_empty() {
+            println!("Errors:");
+            for (kind, stats) in errors {
+                let label = format!("   {}:", kind);
+                println!("{:.<32}{:<10}  {}", label, stats.count, stats.example);
+            }
+        }
+    }
+
+    async fn print_timeline(&self) {
+        println!("Timeline:");
+        println!(
+            "   {:>8} {:>12} {:>8} {:>8} {:>8} {:>8} {:>12}",
+            "Time", "Requests", "Failed", "P(50)", "P(99)", "Max", "Received"
         );
+        for snapshot in self.metrics.snapshots().await {
+            println!(
+                "   {:>7.1}s {:>10.2}/s {:>8} {:>8} {:>8} {:>8} {:>12}",
+                snapshot.elapsed.as_secs_f64(),
+                snapshot.rps(),
+                snapshot.failed_requests,
+                Metrics::format_micros(snapshot.latency.p50),
+                Metrics::format_micros(snapshot.latency.p99),
+                Metrics::format_micros(snapshot.latency.max),
+                self.metrics
+                    .human_readable_bytes(snapshot.bytes_received as f64),
+            );
+        }
     }
 }
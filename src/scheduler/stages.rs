@@ -0,0 +1,147 @@
+use std::{str::FromStr, time::Duration};
+
+/// A single step of a load profile: ramp linearly to `target` over `duration`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    pub duration: Duration,
+    pub target: u64,
+}
+
+/// Multi-stage load profile parsed from `30s:10,2m:200,30s:0`. The target is
+/// the number of active workers in the closed model and the arrival rate in
+/// the open one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stages(Vec<Stage>);
+
+impl Stages {
+    pub fn new(stages: Vec<Stage>) -> Self {
+        Stages(stages)
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.0.iter().map(|stage| stage.duration).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Stage> {
+        self.0.iter()
+    }
+
+    pub fn peak(&self) -> u64 {
+        self.0.iter().map(|stage| stage.target).max().unwrap_or(0)
+    }
+
+    /// Returns the target interpolated between the previous stage's target
+    /// (zero for the first stage) and the current one.
+    pub fn target_at(&self, elapsed: Duration) -> f64 {
+        let mut stage_start = Duration::ZERO;
+        let mut from = 0.0;
+
+        for stage in &self.0 {
+            let to = stage.target as f64;
+            if elapsed < stage_start + stage.duration {
+                let progress = (elapsed - stage_start).as_secs_f64() / stage.duration.as_secs_f64();
+                return from + (to - from) * progress;
+            }
+            stage_start += stage.duration;
+            from = to;
+        }
+
+        from
+    }
+}
+
+impl FromStr for Stages {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut stages = Vec::new();
+
+        for part in s.split(',') {
+            let (duration, target) = part
+                .split_once(':')
+                .ok_or_else(|| format!("invalid stage '{}': expected DURATION:TARGET", part))?;
+
+            let duration = humantime::parse_duration(duration.trim())
+                .map_err(|e| format!("invalid stage duration '{}': {}", duration, e))?;
+            if duration.is_zero() {
+                return Err(format!("stage duration must be positive: '{}'", part));
+            }
+
+            let target = target
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("invalid stage target '{}': {}", target, e))?;
+
+            stages.push(Stage { duration, target });
+        }
+
+        Ok(Stages(stages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(secs: u64, target: u64) -> Stage {
+        Stage {
+            duration: Duration::from_secs(secs),
+            target,
+        }
+    }
+
+    #[test]
+    fn parses_stages() {
+        let cases = [
+            ("30s:10", vec![stage(30, 10)]),
+            (
+                "30s:10,2m:200,30s:0",
+                vec![stage(30, 10), stage(120, 200), stage(30, 0)],
+            ),
+            (" 1m : 5 , 10s:0 ", vec![stage(60, 5), stage(10, 0)]),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                input.parse::<Stages>(),
+                Ok(Stages::new(expected)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_stages() {
+        for input in ["", "30s", "30:10", "0s:10", "30s:-1", "30s:ten", "30s:10,"] {
+            assert!(input.parse::<Stages>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn duration_and_peak() {
+        let stages: Stages = "30s:10,2m:200,30s:0".parse().unwrap();
+
+        assert_eq!(stages.duration(), Duration::from_secs(180));
+        assert_eq!(stages.peak(), 200);
+    }
+
+    #[test]
+    fn interpolates_targets() {
+        let stages: Stages = "10s:100,10s:100,10s:0".parse().unwrap();
+        let cases = [
+            (0, 0.0),
+            (5, 50.0),
+            (10, 100.0),
+            (15, 100.0),
+            (25, 50.0),
+            (30, 0.0),
+            (60, 0.0),
+        ];
+
+        for (secs, expected) in cases {
+            let target = stages.target_at(Duration::from_secs(secs));
+            assert!((target - expected).abs() < 1e-9, "{}s: {}", secs, target);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    requester::cookie::CookieJar,
+    template::feeder::{Feeder, Row},
+};
+
+/// State of the worker that sends a request, available to templates.
+pub struct RequestContext {
+    pub worker_id: u64,
+    /// Number of iterations the worker started so far
+    pub iteration: u64,
+    feeder: Option<Arc<Feeder>>,
+    row: Option<Arc<Row>>,
+    /// Values extracted from responses, kept until the end of the iteration
+    vars: HashMap<String, String>,
+    pub cookies: CookieJar,
+}
+
+impl RequestContext {
+    pub fn new(worker_id: u64, feeder: Option<Arc<Feeder>>) -> Self {
+        RequestContext {
+            worker_id,
+            iteration: 0,
+            feeder,
+            row: None,
+            vars: HashMap::new(),
+            cookies: CookieJar::default(),
+        }
+    }
+
+    /// Starts the worker with the cookies of `cookies`.
+    pub fn with_cookies(mut self, cookies: CookieJar) -> Self {
+        self.cookies = cookies;
+        self
+    }
+
+    /// Starts the next iteration and binds the next row of the data file.
+    /// Values extracted in the previous iteration are dropped, so that a
+    /// journey that failed part-way does not leave its values to the next one.
+    /// Returns `false` when the data file is exhausted and the worker should
+    /// stop.
+    pub fn next_iteration(&mut self) -> bool {
+        if let Some(feeder) = &self.feeder {
+            match feeder.next(self.worker_id, self.iteration) {
+                Some(row) => self.row = Some(row),
+                None => return false,
+            }
+        }
+
+        self.vars.clear();
+        self.cookies.start_iteration();
+        self.iteration += 1;
+        true
+    }
+
+    /// Value of a template variable: an extracted value, or a column of the
+    /// row bound for the current iteration.
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.vars
+            .get(name)
+            .or_else(|| self.row.as_ref().and_then(|row| row.get(name)))
+            .map(String::as_str)
+    }
+
+    pub fn set_var(&mut self, name: String, value: String) {
+        self.vars.insert(name, value);
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{anyhow, Context, Result};
+use rand::RngExt;
+
+/// Values of one row of a data file by column name.
+pub type Row = HashMap<String, String>;
+
+/// Order in which the rows of a data file are handed out to iterations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FeedStrategy {
+    /// Rows in file order shared by all workers, starting over at the end
+    Sequential,
+    /// A random row for every iteration
+    Random,
+    /// Every worker cycles through its own rows, no row is used by two workers
+    UniquePerWorker,
+    /// Every row is used once and the test stops when all of them were used
+    StopWhenExhausted,
+}
+
+/// Rows of a CSV or JSONL file bound to template variables, one row per
+/// iteration.
+pub struct Feeder {
+    rows: Vec<Arc<Row>>,
+    strategy: FeedStrategy,
+    workers: usize,
+    cursor: AtomicUsize,
+    exhausted: AtomicBool,
+}
+
+impl Feeder {
+    /// Reads a `.csv` file with a header row or a `.jsonl` file with one
+    /// object per line. `workers` is the number of workers the rows are split
+    /// between by the unique-per-worker strategy.
+    pub fn load(path: &Path, strategy: FeedStrategy, workers: u64) -> Result<Self> {
+        let parse = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => parse_csv,
+            Some("jsonl") | Some("ndjson") => parse_jsonl,
+            _ => {
+                return Err(anyhow!(
+                    "data file '{}' is not a .csv or .jsonl file",
+                    path.display()
+                ))
+            }
+        };
+
+        std::fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| parse(&content))
+            .and_then(|rows| Feeder::new(rows, strategy, workers))
+            .with_context(|| format!("failed to load data file '{}'", path.display()))
+    }
+
+    pub fn new(rows: Vec<Row>, strategy: FeedStrategy, workers: u64) -> Result<Self> {
+        Feeder::from_rows(rows.into_iter().map(Arc::new).collect(), strategy, workers)
+    }
+
+    /// Feeder over the same rows that starts over at the first one, for a
+    /// new run with the same data split between `workers` workers.
+    pub fn restart(&self, workers: u64) -> Result<Self> {
+        Feeder::from_rows(self.rows.clone(), self.strategy, workers)
+    }
+
+    fn from_rows(rows: Vec<Arc<Row>>, strategy: FeedStrategy, workers: u64) -> Result<Self> {
+        if rows.is_empty() {
+            return Err(anyhow!("the file has no rows"));
+        }
+
+        let workers = workers.max(1) as usize;
+        if strategy == FeedStrategy::UniquePerWorker && rows.len() < workers {
+            return Err(anyhow!(
+                "the file has {} rows, unique-per-worker needs at least one per worker ({})",
+                rows.len(),
+                workers
+            ));
+        }
+
+        Ok(Feeder {
+            rows,
+            strategy,
+            workers,
+            cursor: AtomicUsize::new(0),
+            exhausted: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the row for the `iteration`-th iteration of a worker, or `None`
+    /// once the rows are exhausted.
+    pub fn next(&self, worker_id: u64, iteration: u64) -> Option<Arc<Row>> {
+        let len = self.rows.len();
+
+        let index = match self.strategy {
+            FeedStrategy::Sequential => self.cursor.fetch_add(1, Ordering::Relaxed) % len,
+            FeedStrategy::Random => rand::rng().random_range(0..len),
+            FeedStrategy::UniquePerWorker => {
+                // Worker N uses rows N, N + workers, N + 2 * workers, ...
+                let slot = worker_id as usize % self.workers;
+                let count = (len - slot).div_ceil(self.workers);
+                slot + (iteration as usize % count) * self.workers
+            }
+            FeedStrategy::StopWhenExhausted => {
+                let index = self.cursor.fetch_add(1, Ordering::Relaxed);
+                if index >= len {
+                    self.exhausted.store(true, Ordering::Relaxed);
+                    return None;
+                }
+                index
+            }
+        };
+
+        Some(Arc::clone(&self.rows[index]))
+    }
+
+    /// Names of the columns of the rows, the keys of any row of a JSONL file.
+    pub fn columns(&self) -> HashSet<&str> {
+        self.rows
+            .iter()
+            .flat_map(|row| row.keys().map(String::as_str))
+            .collect()
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+}
+
+fn parse_csv(content: &str) -> Result<Vec<Row>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        rows.push(
+            headers
+                .iter()
+                .zip(record.iter())
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        );
+    }
+
+    Ok(rows)
+}
+
+fn parse_jsonl(content: &str) -> Result<Vec<Row>> {
+    let mut rows = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line)
+            .with_context(|| format!("line {} is not a JSON object", number + 1))?;
+
+        // Nested values are bound as JSON so that they can be sent as they are
+        rows.push(
+            object
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        serde_json::Value::String(text) => text,
+                        value => value.to_string(),
+                    };
+                    (name, value)
+                })
+                .collect(),
+        );
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_csv_and_jsonl() {
+        let cases = [
+            (
+                parse_csv("username,password\nalice,secret1\nbob,\"a,b\"\n").unwrap(),
+                vec![
+                    vec![("username", "alice"), ("password", "secret1")],
+                    vec![("username", "bob"), ("password", "a,b")],
+                ],
+            ),
+            (
+                parse_jsonl("{\"username\": \"alice\", \"id\": 1}\n\n{\"tags\": [\"a\"]}\n")
+                    .unwrap(),
+                vec![
+                    vec![("username", "alice"), ("id", "1")],
+                    vec![("tags", "[\"a\"]")],
+                ],
+            ),
+        ];
+
+        for (rows, expected) in cases {
+            let expected: Vec<Row> = expected
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect()
+                })
+                .collect();
+            assert_eq!(rows, expected);
+        }
+
+        assert!(parse_jsonl("{\"username\": \"alice\"}\n[1, 2]\n").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_rows() {
+        let row = Row::from([("id".to_string(), "1".to_string())]);
+        let cases = [
+            (vec![], FeedStrategy::Sequential, 1, false),
+            (vec![row.clone()], FeedStrategy::Sequential, 2, true),
+            (vec![row.clone()], FeedStrategy::UniquePerWorker, 2, false),
+            (
+                vec![row.clone(), row],
+                FeedStrategy::UniquePerWorker,
+                2,
+                true,
+            ),
+        ];
+
+        for (rows, strategy, workers, valid) in cases {
+            let len = rows.len();
+            assert_eq!(
+                Feeder::new(rows, strategy, workers).is_ok(),
+                valid,
+                "{} rows {:?} {} workers",
+                len,
+                strategy,
+                workers
+            );
+        }
+
+        assert!(Feeder::load(Path::new("rows.txt"), FeedStrategy::Sequential, 1).is_err());
+    }
+
+    #[test]
+    fn hands_out_rows_by_strategy() {
+        // (strategy, rows, workers, (worker, iteration) pairs, expected ids)
+        let cases = [
+            (
+                FeedStrategy::Sequential,
+                3,
+                2,
+                vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)],
+                vec![Some(0), Some(1), Some(2), Some(0), Some(1)],
+            ),
+            (
+                // Worker 0 gets rows 0, 2, 4 and worker 1 rows 1, 3
+                FeedStrategy::UniquePerWorker,
+                5,
+                2,
+                vec![(0, 0), (0, 1), (0, 2), (0, 3), (1, 0), (1, 1), (1, 2)],
+                vec![
+                    Some(0),
+                    Some(2),
+                    Some(4),
+                    Some(0),
+                    Some(1),
+                    Some(3),
+                    Some(1),
+                ],
+            ),
+            (
+                FeedStrategy::StopWhenExhausted,
+                2,
+                1,
+                vec![(0, 0), (0, 1), (0, 2)],
+                vec![Some(0), Some(1), None],
+            ),
+        ];
+
+        for (strategy, rows, workers, calls, expected) in cases {
+            let rows = (0..rows)
+                .map(|id| Row::from([("id".to_string(), id.to_string())]))
+                .collect();
+            let feeder = Feeder::new(rows, strategy, workers).unwrap();
+
+            let ids: Vec<_> = calls
+                .into_iter()
+                .map(|(worker_id, iteration)| {
+                    feeder
+                        .next(worker_id, iteration)
+                        .map(|row| row["id"].parse::<usize>().unwrap())
+                })
+                .collect();
+            assert_eq!(ids, expected, "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn restarts_exhausted_rows() {
+        let rows = vec![Row::from([("id".to_string(), "0".to_string())])];
+        let feeder = Feeder::new(rows, FeedStrategy::StopWhenExhausted, 1).unwrap();
+
+        assert!(feeder.next(0, 0).is_some());
+        assert!(!feeder.is_exhausted());
+        assert!(feeder.next(0, 1).is_none());
+        assert!(feeder.is_exhausted());
+
+        let restarted = feeder.restart(1).unwrap();
+        assert!(!restarted.is_exhausted());
+        assert!(restarted.next(0, 0).is_some());
+    }
+}
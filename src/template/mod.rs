@@ -0,0 +1,4 @@
+pub mod context;
+pub mod feeder;
+#[allow(clippy::module_inception)]
+pub mod template;
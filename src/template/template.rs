@@ -0,0 +1,315 @@
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+use rand::RngExt;
+
+use crate::template::context::RequestContext;
+
+/// Counter behind `{{seq}}`, shared by all workers so that every rendered
+/// value is unique within the run.
+static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+/// Escape of a literal `{{` in a template.
+const ESCAPED_OPEN: &str = "{{{{";
+
+/// A string with `{{...}}` expressions that are rendered for every request,
+/// for example `/users/{{randInt 1 1000}}?request={{uuid}}`. A literal `{{`
+/// is written `{{{{`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    /// Random UUID v4
+    Uuid,
+    /// Random integer between both bounds, inclusive
+    RandInt(i64, i64),
+    /// Number that increases by one on every render
+    Seq,
+    /// Current time in RFC 3339 format
+    NowRfc3339,
+    /// Index of the worker sending the request
+    WorkerId,
+    /// Variable bound for the iteration, such as a column of the data file
+    Var(String),
+}
+
+impl Template {
+    /// Template that renders `source` as it is, for requests sent without
+    /// templating.
+    pub fn literal(source: &str) -> Self {
+        Template {
+            parts: vec![Part::Literal(source.to_string())],
+        }
+    }
+
+    /// Returns the content when the template has no expressions, so that
+    /// callers can prepare the request once instead of on every iteration.
+    pub fn as_static(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [] => Some(""),
+            [Part::Literal(literal)] => Some(literal),
+            _ => None,
+        }
+    }
+
+    /// Names of the variables the template uses.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Expression(Expression::Var(name)) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Renders the template, failing when it uses a variable that is not
+    /// bound for the iteration.
+    pub fn render(&self, context: &RequestContext) -> Result<String, String> {
+        let mut rendered = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Expression(expression) => {
+                    rendered.push_str(&expression.evaluate(context)?);
+                }
+            }
+        }
+
+        Ok(rendered)
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = s;
+
+        while let Some(start) = rest.find("{{") {
+            literal.push_str(&rest[..start]);
+
+            // `{{{{` stands for a literal `{{`
+            if rest[start..].starts_with(ESCAPED_OPEN) {
+                literal.push_str("{{");
+                rest = &rest[start + ESCAPED_OPEN.len()..];
+                continue;
+            }
+
+            let end = rest[start..].find("}}").ok_or_else(|| {
+                format!(
+                    "unclosed '{{{{' in template '{}', write a literal '{{{{' as '{{{{{{{{'",
+                    s
+                )
+            })?;
+            let expression = &rest[start + 2..start + end];
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(Part::Expression(Expression::from_str(expression)?));
+
+            rest = &rest[start + end + 2..];
+        }
+
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template { parts })
+    }
+}
+
+impl Expression {
+    fn evaluate(&self, context: &RequestContext) -> Result<String, String> {
+        let value = match self {
+            Expression::Uuid => uuid::Uuid::new_v4().to_string(),
+            Expression::RandInt(min, max) => rand::rng().random_range(*min..=*max).to_string(),
+            Expression::Seq => SEQUENCE.fetch_add(1, Ordering::Relaxed).to_string(),
+            Expression::NowRfc3339 => {
+                humantime::format_rfc3339_millis(SystemTime::now()).to_string()
+            }
+            Expression::WorkerId => context.worker_id.to_string(),
+            Expression::Var(name) => context
+                .var(name)
+                .ok_or_else(|| format!("undefined template variable '{}'", name))?
+                .to_string(),
+        };
+
+        Ok(value)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words
+            .next()
+            .ok_or_else(|| "empty template expression '{{}}'".to_string())?;
+        let args: Vec<&str> = words.collect();
+
+        let expression = match name {
+            "uuid" => Expression::Uuid,
+            "randInt" => {
+                let [min, max] = args.as_slice() else {
+                    return Err(format!("randInt expects 2 arguments, got '{}'", s.trim()));
+                };
+                let parse = |arg: &str| {
+                    arg.parse::<i64>()
+                        .map_err(|e| format!("invalid randInt argument '{}': {}", arg, e))
+                };
+                let (min, max) = (parse(min)?, parse(max)?);
+                if min > max {
+                    return Err(format!(
+                        "randInt minimum is above the maximum in '{}'",
+                        s.trim()
+                    ));
+                }
+                return Ok(Expression::RandInt(min, max));
+            }
+            "seq" => Expression::Seq,
+            "now_rfc3339" => Expression::NowRfc3339,
+            "worker_id" => Expression::WorkerId,
+            _ if args.is_empty() => return Ok(Expression::Var(name.to_string())),
+            _ => return Err(format!("unknown template function '{}'", name)),
+        };
+
+        if !args.is_empty() {
+            return Err(format!("{} takes no arguments, got '{}'", name, s.trim()));
+        }
+
+        Ok(expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(text: &str) -> Part {
+        Part::Literal(text.to_string())
+    }
+
+    fn var(name: &str) -> Part {
+        Part::Expression(Expression::Var(name.to_string()))
+    }
+
+    #[test]
+    fn parses_templates() {
+        let cases = [
+            ("", vec![]),
+            ("/users", vec![literal("/users")]),
+            ("{{uuid}}", vec![Part::Expression(Expression::Uuid)]),
+            (
+                "/users/{{ randInt 1 1000 }}?id={{seq}}",
+                vec![
+                    literal("/users/"),
+                    Part::Expression(Expression::RandInt(1, 1000)),
+                    literal("?id="),
+                    Part::Expression(Expression::Seq),
+                ],
+            ),
+            (
+                "{{now_rfc3339}}-{{worker_id}}",
+                vec![
+                    Part::Expression(Expression::NowRfc3339),
+                    literal("-"),
+                    Part::Expression(Expression::WorkerId),
+                ],
+            ),
+            ("{{a}}{{b}}", vec![var("a"), var("b")]),
+            ("{{{{literal}}", vec![literal("{{literal}}")]),
+            ("a {{{{ b {{c}}", vec![literal("a {{ b "), var("c")]),
+            ("}} {", vec![literal("}} {")]),
+        ];
+
+        for (input, parts) in cases {
+            assert_eq!(
+                input.parse::<Template>(),
+                Ok(Template { parts }),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        let inputs = [
+            "{{uuid",
+            "{{}}",
+            "{{ }}",
+            "{{randInt 1}}",
+            "{{randInt 1 x}}",
+            "{{randInt 10 1}}",
+            "{{uuid 4}}",
+            "{{unknown 1}}",
+        ];
+
+        for input in inputs {
+            assert!(input.parse::<Template>().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn static_templates() {
+        let cases = [
+            ("", Some("")),
+            ("/users", Some("/users")),
+            ("{{{{x}}", Some("{{x}}")),
+            ("/users/{{id}}", None),
+        ];
+
+        for (input, expected) in cases {
+            let template: Template = input.parse().unwrap();
+            assert_eq!(template.as_static(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn lists_variables() {
+        let template: Template = "{{user}}:{{uuid}}:{{password}}:{{worker_id}}"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            template.variables().collect::<Vec<_>>(),
+            vec!["user", "password"]
+        );
+    }
+
+    #[test]
+    fn renders_templates() {
+        let mut context = RequestContext::new(3, None);
+        context.set_var("id".to_string(), "42".to_string());
+
+        let cases = [
+            ("/users/{{id}}", "/users/42"),
+            ("worker {{worker_id}}", "worker 3"),
+            ("{{randInt 7 7}}", "7"),
+            ("{{{{id}}", "{{id}}"),
+        ];
+
+        for (input, expected) in cases {
+            let template: Template = input.parse().unwrap();
+            assert_eq!(template.render(&context).unwrap(), expected, "{}", input);
+        }
+
+        let template: Template = "{{missing}}".parse().unwrap();
+        assert!(template.render(&context).is_err());
+    }
+}